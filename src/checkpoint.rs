@@ -0,0 +1,184 @@
+use lasso::Spur;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::csr::node_key;
+use crate::error::{Result, WikiSearchError};
+use crate::filter::LinkFilter;
+use crate::format::{self, FileKind};
+use crate::href::HrefStats;
+use crate::progress::BuildStats;
+use crate::{Page, WikiGraph, weighting};
+
+/// Archive entries `get_all` goes through between checkpoints unless told otherwise
+pub const DEFAULT_CHECKPOINT_EVERY: usize = 50_000;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+extern "C" fn on_interrupt(_signal: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::Relaxed);
+    // a second Ctrl-C kills the build without waiting for the checkpoint
+    // SAFETY: `signal` is async-signal-safe
+    unsafe {
+        libc::signal(libc::SIGINT, libc::SIG_DFL);
+    }
+}
+
+/// Makes Ctrl-C ask `get_all` to save a checkpoint and stop rather than kill the process
+pub fn catch_interrupt() {
+    #[cfg(unix)]
+    // SAFETY: the handler only touches an atomic and calls `signal`
+    unsafe {
+        libc::signal(
+            libc::SIGINT,
+            on_interrupt as extern "C" fn(libc::c_int) as libc::sighandler_t,
+        );
+    }
+}
+
+pub(crate) fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
+
+/// What a checkpoint adds to the ones saved before it
+#[derive(Serialize)]
+struct SegmentRef<'a> {
+    entries_done: usize,
+    capture_text: bool,
+    // paths interned since the last checkpoint, in the order they were interned
+    names: Vec<&'a str>,
+    pages: Vec<(Spur, Page)>,
+    aliases: Vec<(Spur, Spur)>,
+    // the counts so far, not since the last checkpoint
+    stats: &'a BuildStats,
+    hrefs: &'a HrefStats,
+}
+
+/// `SegmentRef` read back
+#[derive(Deserialize)]
+struct Segment {
+    entries_done: usize,
+    capture_text: bool,
+    names: Vec<String>,
+    pages: Vec<(Spur, Page)>,
+    aliases: Vec<(Spur, Spur)>,
+    stats: BuildStats,
+    hrefs: HrefStats,
+}
+
+/// What the checkpoint file being appended to already holds
+#[derive(Debug, Default)]
+pub(crate) struct CheckpointLog {
+    // checkpoints saved anywhere else start a new file
+    path: Option<String>,
+    // bytes of complete segments, a failed append may have left more after them
+    len: u64,
+    names: usize,
+    pages: HashSet<Spur>,
+    aliases: HashSet<Spur>,
+}
+
+impl WikiGraph {
+    /// Save the progress of `get_all` to `path` every `entries` archive entries and when
+    /// interrupted with Ctrl-C (see `catch_interrupt`), so `resume` can carry on from there
+    pub fn checkpoint_to(&mut self, path: &str, entries: usize) {
+        self.checkpoint = Some((path.to_string(), entries.max(1)));
+    }
+
+    /// Adds the pages parsed since the last checkpoint to the file at `path`, or starts it
+    /// over if the last checkpoint went elsewhere. Pages are only ever written once, and a
+    /// checkpoint cut short leaves the ones before it readable.
+    pub(crate) fn save_checkpoint(&mut self, path: &str) -> Result<()> {
+        let log = &mut self.checkpointed;
+        if log.path.as_deref() != Some(path) {
+            *log = CheckpointLog {
+                path: Some(path.to_string()),
+                ..CheckpointLog::default()
+            };
+        }
+        let names = (log.names..self.interner.len())
+            .map(|id| self.interner.resolve(&node_key(id as u32)))
+            .collect();
+        let pages: Vec<(Spur, Page)> = self
+            .link_to_page
+            .iter()
+            .filter(|page| !log.pages.contains(page.key()))
+            .map(|page| (*page.key(), page.value().clone()))
+            .collect();
+        let aliases: Vec<(Spur, Spur)> = self
+            .aliases
+            .iter()
+            .filter(|alias| !log.aliases.contains(alias.key()))
+            .map(|alias| (*alias.key(), *alias.value()))
+            .collect();
+        let segment = SegmentRef {
+            entries_done: self.entries_done,
+            capture_text: self.capture_text,
+            names,
+            pages,
+            aliases,
+            stats: &self.build_stats,
+            hrefs: &self.href_stats,
+        };
+
+        log.len = match log.len {
+            0 => format::write_segments(path, FileKind::Checkpoint, &self.header, &segment)?,
+            len => format::append_segment(path, len, &segment)?,
+        };
+        log.names = self.interner.len();
+        log.pages.extend(segment.pages.iter().map(|&(key, _)| key));
+        log.aliases
+            .extend(segment.aliases.iter().map(|&(key, _)| key));
+        Ok(())
+    }
+
+    /// Picks up a build interrupted after saving the checkpoint at `checkpoint_path`, with
+    /// the weighting and link filter it was started with. `get_all` continues from the
+    /// first entry the checkpoint does not cover, and adds its own checkpoints to the file.
+    pub fn resume(zim_path: &str, checkpoint_path: &str) -> Result<Self> {
+        let (header, segments, len): (_, Vec<Segment>, _) =
+            format::read_segments(checkpoint_path, FileKind::Checkpoint)?;
+        let weighter = weighting::from_name(&header.weighting).ok_or_else(|| {
+            WikiSearchError::corrupt_file(
+                checkpoint_path,
+                format!(
+                    "unknown weighting {:?}, the build cannot be resumed",
+                    header.weighting
+                ),
+            )
+        })?;
+        let filter = LinkFilter::new(header.link_filter.clone())?;
+
+        let mut wiki_graph = WikiGraph::new(zim_path, weighter, filter)?;
+        header.check_source(&wiki_graph.a, &mut wiki_graph.warnings)?;
+        wiki_graph.header = header;
+        let mut log = CheckpointLog {
+            path: Some(checkpoint_path.to_string()),
+            len,
+            ..CheckpointLog::default()
+        };
+        for segment in segments {
+            // interning in order gives every name back the Spur its pages were saved with
+            for name in &segment.names {
+                wiki_graph.interner.get_or_intern(name);
+            }
+            for (key, page) in segment.pages {
+                log.pages.insert(key);
+                wiki_graph.link_to_page.insert(key, page);
+            }
+            for (redirect, target) in segment.aliases {
+                log.aliases.insert(redirect);
+                wiki_graph.aliases.insert(redirect, target);
+            }
+            wiki_graph.entries_done = segment.entries_done;
+            wiki_graph.capture_text = segment.capture_text;
+            wiki_graph.build_stats = segment.stats;
+            wiki_graph.href_stats = segment.hrefs;
+        }
+        log.names = wiki_graph.interner.len();
+        wiki_graph.checkpointed = log;
+        Ok(wiki_graph)
+    }
+}
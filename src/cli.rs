@@ -0,0 +1,553 @@
+use clap::{Args, Parser, Subcommand};
+use lasso::Spur;
+use std::io::ErrorKind;
+use std::time::Instant;
+
+use wiki_search::weighting::{DEFAULT_SCROLL_COST, Weighting};
+use wiki_search::{
+    DEFAULT_CHECKPOINT_EVERY, DEFAULT_HUB_STRENGTH, DEFAULT_LANDMARK_COUNT, FileKind, FilterPreset,
+    GraphHeader, HubMeasure, HubPenalty, JsonSummary, LandmarkSelection, LinkFilter, LinkRegion,
+    PageRankConfig, PathConstraints, Result, TerminalProgress, WikiGraph, WikiSearchError,
+    catch_interrupt, serve, side_path,
+};
+
+const WIKI_GRAPH_PATH: &str = "wiki-graph";
+const ZIM_PATH: &str = "wikipedia_en_simple_all_nopic_2025-09.zim";
+
+/// Explore the link graph of a Wikipedia ZIM archive
+#[derive(Debug, Parser)]
+#[command(version)]
+pub struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+/// Where the archive and the graph built from it live
+#[derive(Debug, Args)]
+struct Paths {
+    /// ZIM archive the graph is built from
+    #[arg(long, default_value = ZIM_PATH)]
+    zim: String,
+    /// Graph file, landmarks and PageRank scores are stored next to it
+    #[arg(long, default_value = WIKI_GRAPH_PATH)]
+    graph: String,
+}
+
+impl Paths {
+    fn load(&self) -> Result<WikiGraph> {
+        let wiki_graph = WikiGraph::load_bin(&self.zim, &self.graph)?;
+        print_warnings(&wiki_graph);
+        Ok(wiki_graph)
+    }
+}
+
+fn print_warnings(wiki_graph: &WikiGraph) {
+    for warning in wiki_graph.warnings() {
+        eprintln!("Warning: {warning}");
+    }
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Parse every article of the archive and write the graph file
+    Build {
+        #[command(flatten)]
+        paths: Paths,
+        /// How the position of a link turns into the cost of following it
+        #[arg(long, value_enum, default_value_t = Weighting::Linear)]
+        weighting: Weighting,
+        /// Cost of every link above the followed one, for `--weighting absolute`
+        #[arg(long, default_value_t = DEFAULT_SCROLL_COST)]
+        scroll_cost: f32,
+        /// Links to leave out, tuned for each kind of archive
+        #[arg(long, value_enum, default_value_t = FilterPreset::Wikipedia)]
+        filter: FilterPreset,
+        /// Also drop links inside elements matching this CSS selector
+        #[arg(long)]
+        exclude_selector: Vec<String>,
+        /// Also drop links whose href matches this glob, e.g. `List_of_*`
+        #[arg(long)]
+        exclude_href: Vec<String>,
+        /// Also drop links in these regions of an article: lead, section, infobox, table,
+        /// list, navbox or footer
+        #[arg(long, value_delimiter = ',')]
+        exclude_region: Vec<String>,
+        /// Keep the anchor text and sentence of every link, for `path --explain`
+        #[arg(long)]
+        context: bool,
+        /// Carry on from the checkpoint of an interrupted build, with the options it was
+        /// started with
+        #[arg(long, conflicts_with_all = [
+            "weighting", "scroll_cost", "filter", "exclude_selector", "exclude_href",
+            "exclude_region", "context",
+        ])]
+        resume: bool,
+        /// Archive entries to go through between checkpoints
+        #[arg(long, default_value_t = DEFAULT_CHECKPOINT_EVERY)]
+        checkpoint_every: usize,
+    },
+    /// Shortest path of links from one page to another
+    Path {
+        #[command(flatten)]
+        paths: Paths,
+        from: String,
+        to: String,
+        /// Show the sentence each link appears in, needs a graph built with `--context`
+        #[arg(long)]
+        explain: bool,
+        /// Also show the next shortest paths that visit no page twice, up to this many in all
+        #[arg(long, default_value_t = 1)]
+        k: usize,
+        /// Never go through this page
+        #[arg(long, conflicts_with = "k")]
+        avoid: Vec<String>,
+        /// Never go through pages matching this glob, `#` matches a digit, e.g. `####`
+        #[arg(long, conflicts_with = "k")]
+        avoid_pattern: Vec<String>,
+        /// Go through this page, repeat to visit several in order
+        #[arg(long, conflicts_with = "k")]
+        via: Vec<String>,
+        /// Follow at most this many links
+        #[arg(long, conflicts_with = "k")]
+        max_hops: Option<usize>,
+        /// Make going through a page cost more the more of a hub it is by this measure
+        #[arg(
+            long,
+            value_enum,
+            conflicts_with_all = ["k", "avoid", "avoid_pattern", "via", "max_hops"]
+        )]
+        hub_penalty: Option<HubMeasure>,
+        /// Penalty of the biggest hub, the others pay in proportion to their score
+        #[arg(long, default_value_t = DEFAULT_HUB_STRENGTH, requires = "hub_penalty")]
+        hub_strength: f32,
+    },
+    /// Pages within a link distance of a page, closest first
+    Neighbors {
+        #[command(flatten)]
+        paths: Paths,
+        page: String,
+        #[arg(long, default_value_t = 0.0)]
+        min: f32,
+        #[arg(long, default_value_t = 3.0)]
+        max: f32,
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+        /// Pick `limit` pages at random from the range instead of the closest ones
+        #[arg(long)]
+        sample: bool,
+    },
+    /// Pages linking to a page, most prominent links first
+    Backlinks {
+        #[command(flatten)]
+        paths: Paths,
+        page: String,
+        #[arg(long)]
+        limit: Option<usize>,
+    },
+    /// Random articles that are part of the graph
+    Random {
+        #[command(flatten)]
+        paths: Paths,
+        #[arg(long, default_value_t = 1)]
+        count: usize,
+    },
+    /// Size of the graph
+    Stats {
+        #[command(flatten)]
+        paths: Paths,
+    },
+    /// How the graph file was built and whether it matches the archive
+    Info {
+        #[command(flatten)]
+        paths: Paths,
+    },
+    /// Precompute landmarks to speed up `path`
+    Landmarks {
+        #[command(flatten)]
+        paths: Paths,
+        #[arg(long, default_value_t = DEFAULT_LANDMARK_COUNT)]
+        count: usize,
+        #[arg(long, value_enum, default_value_t = LandmarkSelection::Farthest)]
+        selection: LandmarkSelection,
+    },
+    /// Precompute PageRank and show the top pages
+    Pagerank {
+        #[command(flatten)]
+        paths: Paths,
+        /// Favour links near the top of a page
+        #[arg(long)]
+        weighted: bool,
+        #[arg(long, default_value_t = 20)]
+        top: usize,
+    },
+    /// Pages most related to a page by personalized PageRank
+    Related {
+        #[command(flatten)]
+        paths: Paths,
+        page: String,
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+        #[arg(long)]
+        weighted: bool,
+    },
+    /// Full-text search, matching articles are shown with their graph node
+    Search {
+        #[command(flatten)]
+        paths: Paths,
+        query: String,
+        #[arg(long, default_value_t = 0)]
+        offset: usize,
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// Titles completing a prefix, for picking start and target articles
+    Suggest {
+        #[command(flatten)]
+        paths: Paths,
+        prefix: String,
+        #[arg(long, default_value_t = 10)]
+        limit: usize,
+    },
+    /// Serve path and neighborhood queries as a JSON API over HTTP
+    Serve {
+        #[command(flatten)]
+        paths: Paths,
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        addr: String,
+        /// Worker threads answering requests, one per core by default
+        #[arg(long)]
+        threads: Option<usize>,
+    },
+}
+
+pub fn run() {
+    let cli = Cli::parse();
+    if let Err(e) = execute(cli.command) {
+        eprintln!("Error: {e}");
+        std::process::exit(1);
+    }
+}
+
+fn execute(command: Command) -> Result<()> {
+    match command {
+        Command::Build {
+            paths,
+            weighting,
+            scroll_cost,
+            filter,
+            exclude_selector,
+            exclude_href,
+            exclude_region,
+            context,
+            resume,
+            checkpoint_every,
+        } => {
+            let checkpoint_path = side_path(&paths.graph, FileKind::Checkpoint);
+            let mut wiki_graph = match resume {
+                true => {
+                    let wiki_graph = WikiGraph::resume(&paths.zim, &checkpoint_path)?;
+                    print_warnings(&wiki_graph);
+                    wiki_graph
+                }
+                false => {
+                    let mut config = filter.config();
+                    config.extend(&exclude_selector, &exclude_href, &exclude_region);
+                    let mut wiki_graph = WikiGraph::new(
+                        &paths.zim,
+                        weighting.weighter(scroll_cost)?,
+                        LinkFilter::new(config)?,
+                    )?;
+                    wiki_graph.capture_link_text(context);
+                    wiki_graph
+                }
+            };
+            wiki_graph.checkpoint_to(&checkpoint_path, checkpoint_every);
+            wiki_graph.observe_build(Box::new(TerminalProgress::default()));
+            wiki_graph.observe_build(Box::new(JsonSummary::next_to(&paths.graph)));
+            catch_interrupt();
+            if !wiki_graph.get_all()? {
+                println!("Progress saved to {checkpoint_path}, continue with `build --resume`");
+                return Ok(());
+            }
+            println!("Got {} links", wiki_graph.edge_count());
+            wiki_graph.save_bin(&paths.graph)?;
+            match std::fs::remove_file(&checkpoint_path) {
+                Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+                _ => Ok(()),
+            }
+        }
+        Command::Path {
+            paths,
+            from,
+            to,
+            explain,
+            k,
+            avoid,
+            avoid_pattern,
+            via,
+            max_hops,
+            hub_penalty,
+            hub_strength,
+        } => {
+            let wiki_graph = paths.load()?;
+            let (first_link, target_link) = (
+                wiki_graph.resolve_name(&from)?,
+                wiki_graph.resolve_name(&to)?,
+            );
+            println!(
+                "{} -> {}\n",
+                wiki_graph.resolve(first_link),
+                wiki_graph.resolve(target_link)
+            );
+            let constraints = PathConstraints {
+                avoid: avoid
+                    .iter()
+                    .map(|name| wiki_graph.resolve_name(name))
+                    .collect::<Result<_>>()?,
+                avoid_patterns: avoid_pattern,
+                via: via
+                    .iter()
+                    .map(|name| wiki_graph.resolve_name(name))
+                    .collect::<Result<_>>()?,
+                max_hops,
+            };
+            let found = match (hub_penalty, constraints.is_empty()) {
+                (Some(measure), _) => {
+                    let penalty = HubPenalty {
+                        measure,
+                        strength: hub_strength,
+                    };
+                    wiki_graph
+                        .find_shortest_path_penalized(first_link, target_link, penalty)?
+                        .map(|path| wiki_graph.path_info(path))
+                        .into_iter()
+                        .collect()
+                }
+                (None, true) => wiki_graph.find_k_shortest_paths(first_link, target_link, k),
+                (None, false) => wiki_graph
+                    .find_shortest_path_with(first_link, target_link, &constraints)
+                    .map(|path| wiki_graph.path_info(path))
+                    .into_iter()
+                    .collect(),
+            };
+            if found.is_empty() {
+                println!("No path exists");
+            }
+            for (i, info) in found.iter().enumerate() {
+                let p = &info.path;
+                if k > 1 {
+                    if i > 0 {
+                        println!();
+                    }
+                    println!("#{} (distance {:.4})", i + 1, info.distance);
+                }
+                match explain {
+                    true => {
+                        println!("{}", wiki_graph.resolve(p[0]));
+                        for hop in p.windows(2) {
+                            match wiki_graph.link_text(hop[0], hop[1]) {
+                                Some(text) if !text.context.is_empty() => {
+                                    println!("  \"{}\" [{}]", text.context, text.anchor)
+                                }
+                                _ => println!("  (no context recorded)"),
+                            }
+                            println!("{}", wiki_graph.resolve(hop[1]));
+                        }
+                    }
+                    false => {
+                        for &link in p {
+                            println!("{}", wiki_graph.resolve(link));
+                        }
+                    }
+                }
+            }
+            Ok(())
+        }
+        Command::Neighbors {
+            paths,
+            page,
+            min,
+            max,
+            limit,
+            sample,
+        } => {
+            let wiki_graph = paths.load()?;
+            let page = wiki_graph.resolve_name(&page)?;
+            let neighbors = match sample {
+                true => wiki_graph.get_close_titles(page, limit, min, max),
+                false => wiki_graph
+                    .iter_close_titles(page, min, Some(max))
+                    .take(limit)
+                    .collect(),
+            };
+            for info in neighbors {
+                let last = info.path.last().copied().unwrap_or(page);
+                println!("{:.4} {}", info.distance, wiki_graph.resolve(last));
+            }
+            Ok(())
+        }
+        Command::Backlinks { paths, page, limit } => {
+            let wiki_graph = paths.load()?;
+            let page = wiki_graph.resolve_name(&page)?;
+            // most prominent links first
+            let mut backlinks: Vec<(Spur, f32)> = wiki_graph.inlinks(page).collect();
+            backlinks.sort_by(|a, b| a.1.total_cmp(&b.1));
+            println!(
+                "{} pages link to {}\n",
+                wiki_graph.in_degree(page),
+                wiki_graph.resolve(page)
+            );
+            for (link, weight) in backlinks.into_iter().take(limit.unwrap_or(usize::MAX)) {
+                let region = wiki_graph
+                    .link_region(link, page)
+                    .unwrap_or(LinkRegion::Lead);
+                println!(
+                    "{:.4} {:<10} {}",
+                    weight,
+                    region.to_string(),
+                    wiki_graph.resolve(link)
+                );
+            }
+            Ok(())
+        }
+        Command::Random { paths, count } => {
+            let wiki_graph = paths.load()?;
+            for _ in 0..count {
+                let page = wiki_graph
+                    .random_page(1000)
+                    .ok_or(WikiSearchError::EmptyGraph)?;
+                println!("{}", wiki_graph.resolve(page));
+            }
+            Ok(())
+        }
+        Command::Stats { paths } => {
+            let wiki_graph = paths.load()?;
+            let (nodes, pages, edges) = (
+                wiki_graph.node_count(),
+                wiki_graph.page_count(),
+                wiki_graph.edge_count(),
+            );
+            println!("nodes:           {nodes}");
+            println!("pages:           {pages}");
+            println!("links:           {edges}");
+            println!("redirects:       {}", wiki_graph.redirect_count());
+            println!("avg out-degree:  {:.2}", edges as f64 / pages.max(1) as f64);
+            println!("landmarks:       {}", wiki_graph.has_landmarks());
+            println!("pagerank:        {}", wiki_graph.has_pagerank());
+            Ok(())
+        }
+        Command::Info { paths } => {
+            let header = GraphHeader::read(&paths.graph)?;
+            let source = &header.source;
+            println!("graph:           {}", paths.graph);
+            println!("built from:      {}", source.filename);
+            println!("uuid:            {}", source.uuid);
+            println!(
+                "checksum:        {}",
+                source.checksum.as_deref().unwrap_or("none")
+            );
+            println!("entries:         {}", source.entry_count);
+            println!("weighting:       {}", header.weighting);
+            println!("link filter:     {}", header.link_filter.name);
+            println!("built at:        {} (unix time)", header.built_at);
+            println!("builder version: {}", header.builder_version);
+            match header.check_archive(&paths.zim) {
+                Ok(warnings) if warnings.is_empty() => println!("{} matches the graph", paths.zim),
+                Ok(warnings) => warnings.iter().for_each(|warning| println!("{warning}")),
+                Err(e) => println!("{e}"),
+            }
+            Ok(())
+        }
+        Command::Landmarks {
+            paths,
+            count,
+            selection,
+        } => {
+            let mut wiki_graph = paths.load()?;
+            let start = Instant::now();
+            wiki_graph.compute_landmarks(count, selection);
+            eprintln!("Computed {count} landmarks in {:?}", start.elapsed());
+            wiki_graph.save_landmarks(&paths.graph)
+        }
+        Command::Pagerank {
+            paths,
+            weighted,
+            top,
+        } => {
+            let mut wiki_graph = paths.load()?;
+            let start = Instant::now();
+            wiki_graph.compute_pagerank(&PageRankConfig {
+                weighted,
+                ..PageRankConfig::default()
+            });
+            eprintln!("Computed PageRank in {:?}", start.elapsed());
+            for (page, score) in wiki_graph.top_pages(top) {
+                println!("{:.6} {}", score, wiki_graph.resolve(page));
+            }
+            wiki_graph.save_pagerank(&paths.graph)
+        }
+        Command::Related {
+            paths,
+            page,
+            limit,
+            weighted,
+        } => {
+            let wiki_graph = paths.load()?;
+            let page = wiki_graph.resolve_name(&page)?;
+            let config = PageRankConfig {
+                weighted,
+                ..PageRankConfig::default()
+            };
+            for (link, score) in wiki_graph.related_articles(page, limit, &config) {
+                println!("{:.6} {}", score, wiki_graph.resolve(link));
+            }
+            Ok(())
+        }
+        Command::Search {
+            paths,
+            query,
+            offset,
+            limit,
+        } => {
+            let wiki_graph = paths.load()?;
+            let results = wiki_graph.search(&query)?;
+            println!("about {} matches\n", results.estimated_matches());
+            for hit in results.starting_at(offset).take(limit) {
+                let hit = hit?;
+                match hit.node {
+                    Some(node) => println!("{} ({node})", hit.title),
+                    None => println!("{} (not in the graph)", hit.title),
+                }
+            }
+            Ok(())
+        }
+        Command::Suggest {
+            paths,
+            prefix,
+            limit,
+        } => {
+            let wiki_graph = paths.load()?;
+            for suggestion in wiki_graph.suggest(&prefix, limit)? {
+                let marker = match suggestion.in_graph {
+                    true => "",
+                    false => " (not in the graph)",
+                };
+                println!("{} [{}]{marker}", suggestion.title, suggestion.path);
+                if let Some(snippet) = suggestion.snippet {
+                    println!("    {snippet}");
+                }
+            }
+            Ok(())
+        }
+        Command::Serve {
+            paths,
+            addr,
+            threads,
+        } => {
+            let wiki_graph = paths.load()?;
+            let threads = threads
+                .unwrap_or_else(|| std::thread::available_parallelism().map_or(4, |n| n.get()));
+            serve(wiki_graph, &addr, threads)
+        }
+    }
+}
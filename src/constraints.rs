@@ -0,0 +1,83 @@
+use lasso::Spur;
+use std::collections::{HashMap, HashSet};
+
+use crate::csr::{node_id, node_key};
+use crate::filter::glob_match;
+use crate::{WikiGraph, href, paths};
+
+/// Limits on the route `WikiGraph::find_shortest_path_with` may take
+#[derive(Debug, Clone, Default)]
+pub struct PathConstraints {
+    /// Pages the path may not go through
+    pub avoid: HashSet<Spur>,
+    /// Pages whose path matches any of these globs are avoided too, `*` matches any run of
+    /// characters and `#` any digit, e.g. `List_of_*` or `####` for years
+    pub avoid_patterns: Vec<String>,
+    /// Pages the path must go through, in this order
+    pub via: Vec<Spur>,
+    /// Most links the whole path may follow
+    pub max_hops: Option<usize>,
+}
+
+impl PathConstraints {
+    pub fn is_empty(&self) -> bool {
+        self.avoid.is_empty()
+            && self.avoid_patterns.is_empty()
+            && self.via.is_empty()
+            && self.max_hops.is_none()
+    }
+}
+
+impl WikiGraph {
+    /// Whether the article name of `node` matches any of `patterns`
+    fn matches_pattern(&self, node: u32, patterns: &[String], new_namespace_scheme: bool) -> bool {
+        let name = href::article_name(self.resolve(node_key(node)), new_namespace_scheme);
+        patterns.iter().any(|pattern| glob_match(pattern, name))
+    }
+
+    /// Like `find_shortest_path`, but only among paths that meet `constraints`.
+    ///
+    /// The constraints are checked as the search runs, so the result is the shortest
+    /// path that meets them rather than a filtered shortest path. The start is never
+    /// avoided, waypoints and the target can be.
+    ///
+    /// With waypoints the result is the shortest walk through them and may visit a page
+    /// twice, e.g. to come back out of a waypoint that only links back the way it came.
+    /// Without waypoints it never does.
+    pub fn find_shortest_path_with(
+        &self,
+        first_link: Spur,
+        target_link: Spur,
+        constraints: &PathConstraints,
+    ) -> Option<Vec<Spur>> {
+        let avoid: HashSet<u32> = constraints
+            .avoid
+            .iter()
+            .map(|&page| node_id(self.canonical(page)))
+            .collect();
+        let via: Vec<u32> = constraints
+            .via
+            .iter()
+            .map(|&page| node_id(self.canonical(page)))
+            .collect();
+        let patterns = &constraints.avoid_patterns;
+        let new_namespace_scheme = self.a.has_new_namespace_scheme();
+        // the search reaches most nodes through several links, match each one once
+        let mut matched: HashMap<u32, bool> = HashMap::new();
+        let (_, path) = paths::constrained_dijkstra(
+            &self.graph,
+            node_id(self.canonical(first_link)),
+            node_id(self.canonical(target_link)),
+            &via,
+            constraints.max_hops,
+            |node| {
+                !avoid.contains(&node)
+                    && (patterns.is_empty()
+                        || !*matched.entry(node).or_insert_with(|| {
+                            self.matches_pattern(node, patterns, new_namespace_scheme)
+                        }))
+            },
+        )?;
+        Some(path.into_iter().map(node_key).collect())
+    }
+}
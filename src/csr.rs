@@ -0,0 +1,314 @@
+use dashmap::DashMap;
+use lasso::{Key, Spur};
+
+use crate::Page;
+use crate::error::{Result, WikiSearchError};
+use crate::extract::LinkRegion;
+use crate::storage::{self, Section, valid_offsets};
+
+/// Dense node id of an interned path, ids are the interner indices
+pub fn node_id(key: Spur) -> u32 {
+    key.into_usize() as u32
+}
+
+pub fn node_key(node: u32) -> Spur {
+    Spur::try_from_usize(node as usize).unwrap()
+}
+
+/// Read-only adjacency of the link graph in compressed sparse row form.
+///
+/// The outlinks of node `n` are `targets[offsets[n]..offsets[n + 1]]`, with the
+/// matching edge weights and link regions at the same positions in `weights` and `regions`.
+#[derive(Debug, Default)]
+pub struct CsrGraph {
+    offsets: Section<u64>,
+    targets: Section<u32>,
+    weights: Section<f32>,
+    // `LinkRegion::to_byte` of every edge
+    regions: Section<u8>,
+}
+
+impl CsrGraph {
+    /// Freezes the pages into a graph with `node_count` rows (one per interned string).
+    pub(crate) fn from_pages(pages: &DashMap<Spur, Page>, node_count: usize) -> Self {
+        let mut offsets = vec![0_u64; node_count + 1];
+        for page in pages.iter() {
+            offsets[page.key().into_usize() + 1] = page.links_to_weight.len() as u64;
+        }
+        for i in 1..offsets.len() {
+            offsets[i] += offsets[i - 1];
+        }
+
+        let edge_count = offsets[node_count] as usize;
+        let mut targets = vec![0_u32; edge_count];
+        let mut weights = vec![0_f32; edge_count];
+        let mut regions = vec![0_u8; edge_count];
+        for page in pages.iter() {
+            let start = offsets[page.key().into_usize()] as usize;
+            // keep rows in document order so they read like the article
+            let mut links: Vec<_> = page.links_to_weight.iter().collect();
+            links.sort_unstable_by_key(|(_, info)| info.index);
+            for (i, (link, info)) in links.into_iter().enumerate() {
+                targets[start + i] = node_id(*link);
+                weights[start + i] = info.weight;
+                regions[start + i] = info.region.to_byte();
+            }
+        }
+
+        CsrGraph {
+            offsets: offsets.into(),
+            targets: targets.into(),
+            weights: weights.into(),
+            regions: regions.into(),
+        }
+    }
+
+    /// Checks the sections read back have the shape of a graph. Rows and targets are only
+    /// checked as they are read, so loading a graph does not go through every edge.
+    pub fn from_sections(
+        offsets: Section<u64>,
+        targets: Section<u32>,
+        weights: Section<f32>,
+        regions: Section<u8>,
+    ) -> Result<Self> {
+        if !valid_offsets(&offsets, targets.len())
+            || weights.len() != targets.len()
+            || regions.len() != targets.len()
+        {
+            return Err(WikiSearchError::CorruptGraph(
+                "adjacency is inconsistent".to_string(),
+            ));
+        }
+        Ok(CsrGraph {
+            offsets,
+            targets,
+            weights,
+            regions,
+        })
+    }
+
+    pub fn sections(&self) -> [&[u8]; 4] {
+        [
+            self.offsets.as_bytes(),
+            self.targets.as_bytes(),
+            self.weights.as_bytes(),
+            self.regions.as_bytes(),
+        ]
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.offsets.len().saturating_sub(1)
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.targets.len()
+    }
+
+    /// Positions of the outlinks of `node` among all edges, for data stored per edge
+    pub fn row(&self, node: u32) -> std::ops::Range<usize> {
+        // nodes interned after freezing have no row yet
+        storage::row(&self.offsets, node as usize)
+    }
+
+    pub fn out_degree(&self, node: u32) -> usize {
+        self.row(node).len()
+    }
+
+    /// Outgoing `(target, weight)` pairs of `node` in document order
+    pub fn outlinks(&self, node: u32) -> impl Iterator<Item = (u32, f32)> + '_ {
+        let row = self.row(node);
+        let node_count = self.node_count();
+        self.targets[row.clone()]
+            .iter()
+            .copied()
+            .zip(self.weights[row].iter().copied())
+            // only a corrupt file links to a node that does not exist
+            .filter(move |&(target, _)| (target as usize) < node_count)
+    }
+
+    /// Position of the edge from `from` to `to` among all edges
+    pub fn find_edge(&self, from: u32, to: u32) -> Option<usize> {
+        let row = self.row(from);
+        let i = self.targets[row.clone()].iter().position(|&t| t == to)?;
+        Some(row.start + i)
+    }
+
+    /// Region of the source article each outlink of `node` was found in, same order as `outlinks`
+    pub fn outlink_regions(&self, node: u32) -> impl Iterator<Item = LinkRegion> + '_ {
+        self.regions[self.row(node)]
+            .iter()
+            .map(|&byte| LinkRegion::from_byte(byte))
+    }
+
+    /// The same graph with every edge reversed, rows then list a node's inlinks.
+    pub fn transpose(&self) -> CsrGraph {
+        let node_count = self.node_count();
+        let mut offsets = vec![0_u64; node_count + 1];
+        for &target in self.targets.iter() {
+            offsets[target as usize + 1] += 1;
+        }
+        for i in 1..offsets.len() {
+            offsets[i] += offsets[i - 1];
+        }
+
+        let mut next: Vec<u64> = offsets[..node_count].to_vec();
+        let mut targets = vec![0_u32; self.edge_count()];
+        let mut weights = vec![0_f32; self.edge_count()];
+        let mut regions = vec![0_u8; self.edge_count()];
+        for source in 0..node_count as u32 {
+            let row = self.row(source);
+            for (edge, (target, weight)) in row.zip(self.outlinks(source)) {
+                let slot = &mut next[target as usize];
+                targets[*slot as usize] = source;
+                weights[*slot as usize] = weight;
+                regions[*slot as usize] = self.regions[edge];
+                *slot += 1;
+            }
+        }
+
+        CsrGraph {
+            offsets: offsets.into(),
+            targets: targets.into(),
+            weights: weights.into(),
+            regions: regions.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+impl CsrGraph {
+    /// A graph of `node_count` nodes with `(from, to, weight)` edges, in that order per row
+    pub(crate) fn from_edges(node_count: usize, edges: &[(u32, u32, f32)]) -> Self {
+        let mut edges = edges.to_vec();
+        edges.sort_by_key(|&(from, _, _)| from);
+        let mut offsets = vec![0_u64; node_count + 1];
+        for &(from, _, _) in &edges {
+            offsets[from as usize + 1] += 1;
+        }
+        for i in 1..offsets.len() {
+            offsets[i] += offsets[i - 1];
+        }
+        // a few different regions so they can be told apart once moved around
+        let regions: Vec<u8> = (0..edges.len()).map(|i| (i % 4) as u8).collect();
+        CsrGraph::from_sections(
+            offsets.into(),
+            edges
+                .iter()
+                .map(|&(_, to, _)| to)
+                .collect::<Vec<_>>()
+                .into(),
+            edges.iter().map(|&(_, _, w)| w).collect::<Vec<_>>().into(),
+            regions.into(),
+        )
+        .unwrap()
+    }
+
+    /// A random graph with up to `max_degree` distinct outlinks per node
+    pub(crate) fn random(seed: u64, node_count: usize, max_degree: usize) -> Self {
+        use rand::{Rng, SeedableRng, rngs::StdRng};
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut edges = Vec::new();
+        for from in 0..node_count as u32 {
+            let mut targets: Vec<u32> = (0..rng.random_range(0..=max_degree))
+                .map(|_| rng.random_range(0..node_count as u32))
+                .collect();
+            targets.sort_unstable();
+            targets.dedup();
+            edges.extend(
+                targets
+                    .into_iter()
+                    .map(|to| (from, to, rng.random::<f32>())),
+            );
+        }
+        CsrGraph::from_edges(node_count, &edges)
+    }
+
+    /// Every `(from, to, weight, region)` edge in row order
+    fn edges(&self) -> Vec<(u32, u32, f32, LinkRegion)> {
+        (0..self.node_count() as u32)
+            .flat_map(|from| {
+                self.outlinks(from)
+                    .zip(self.outlink_regions(from))
+                    .map(move |((to, weight), region)| (from, to, weight, region))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> CsrGraph {
+        CsrGraph::from_edges(
+            4,
+            &[
+                (0, 1, 0.5),
+                (0, 2, 0.1),
+                (1, 2, 0.0),
+                (2, 0, 0.3),
+                (3, 0, 1.0),
+                (3, 2, 0.2),
+            ],
+        )
+    }
+
+    #[test]
+    fn transpose_reverses_every_edge() {
+        let graph = sample();
+        let reverse = graph.transpose();
+        assert_eq!(reverse.node_count(), 4);
+        assert_eq!(reverse.edge_count(), graph.edge_count());
+        let mut reversed: Vec<_> = graph
+            .edges()
+            .into_iter()
+            .map(|(from, to, weight, region)| (to, from, weight, region))
+            .collect();
+        reversed.sort_by_key(|&(from, to, _, _)| (from, to));
+        assert_eq!(reverse.edges(), reversed);
+        assert_eq!(
+            reverse.outlinks(2).collect::<Vec<_>>(),
+            vec![(0, 0.1), (1, 0.0), (3, 0.2)]
+        );
+        assert_eq!(reverse.out_degree(3), 0);
+    }
+
+    #[test]
+    fn transpose_twice_gives_the_graph_back() {
+        let graph = sample();
+        let mut edges = graph.edges();
+        edges.sort_by_key(|&(from, to, _, _)| (from, to));
+        assert_eq!(graph.transpose().transpose().edges(), edges);
+    }
+
+    #[test]
+    fn rows_outside_the_graph_are_empty() {
+        let graph = sample();
+        assert_eq!(graph.out_degree(4), 0);
+        assert_eq!(graph.find_edge(0, 2), Some(1));
+        assert_eq!(graph.find_edge(1, 0), None);
+    }
+
+    #[test]
+    fn corrupt_adjacency_is_rejected_or_read_safely() {
+        let load = |offsets: Vec<u64>, targets: Vec<u32>| {
+            let edges = targets.len();
+            CsrGraph::from_sections(
+                offsets.into(),
+                targets.into(),
+                vec![0.0; edges].into(),
+                vec![0; edges].into(),
+            )
+        };
+        assert!(load(vec![0, 1, 2, 3], vec![1, 2, 0]).is_ok());
+        // offsets not covering every edge
+        assert!(load(vec![0, 1, 2], vec![1, 2, 0]).is_err());
+        assert!(load(vec![], vec![]).is_err());
+
+        // rows out of order and links to nodes that do not exist are only found when read
+        let graph = load(vec![0, 2, 1, 3], vec![1, 5, 0]).unwrap();
+        assert_eq!(graph.outlinks(0).collect::<Vec<_>>(), vec![(1, 0.0)]);
+        assert_eq!(graph.out_degree(1), 0);
+        assert_eq!(graph.outlinks(2).collect::<Vec<_>>(), vec![(0, 0.0)]);
+    }
+}
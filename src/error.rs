@@ -0,0 +1,112 @@
+use crate::resolver::Unresolved;
+
+/// Everything that can go wrong opening, building, loading or querying a graph
+#[derive(Debug)]
+pub enum WikiSearchError {
+    /// The ZIM archive at `path` could not be opened
+    ArchiveOpen {
+        path: String,
+    },
+    /// A graph was opened with an archive other than the one it was built from
+    ArchiveMismatch {
+        expected: String,
+        found: String,
+    },
+    /// libzim failed to do something with an archive that did open
+    Archive(String),
+    /// The archive has no entry at `path`, or no item behind it
+    MissingEntry {
+        path: String,
+    },
+    /// The content of the entry at `path` is not UTF-8, so not an article
+    NonUtf8 {
+        path: String,
+    },
+    /// `path` is not a file we wrote, or it was cut short or damaged
+    CorruptFile {
+        path: String,
+        reason: String,
+    },
+    /// The sections of a mapped graph do not fit together
+    CorruptGraph(String),
+    /// `path` was written in format `found` but this build reads `expected`
+    VersionMismatch {
+        path: String,
+        found: u32,
+        expected: u32,
+    },
+    /// No node of the graph goes by the name asked for
+    UnknownNode(Unresolved),
+    /// There is no page to pick, e.g. for a random article
+    EmptyGraph,
+    /// A link filter, weighting or parameter that cannot be used
+    InvalidInput(String),
+    /// The archive lacks what was asked of it, e.g. a full-text index
+    Unsupported(String),
+    Io(std::io::Error),
+}
+
+pub type Result<T> = std::result::Result<T, WikiSearchError>;
+
+impl WikiSearchError {
+    pub fn corrupt_file(path: &str, reason: impl Into<String>) -> Self {
+        WikiSearchError::CorruptFile {
+            path: path.to_string(),
+            reason: reason.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for WikiSearchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WikiSearchError::ArchiveOpen { path } => write!(f, "{path}: cannot open ZIM archive"),
+            WikiSearchError::ArchiveMismatch { expected, found } => write!(
+                f,
+                "graph was built from {expected} but the archive opened is {found}"
+            ),
+            WikiSearchError::Archive(msg) => write!(f, "{msg}"),
+            WikiSearchError::MissingEntry { path } => write!(f, "no entry {path} in the archive"),
+            WikiSearchError::NonUtf8 { path } => write!(f, "{path} is not UTF-8 text"),
+            WikiSearchError::CorruptFile { path, reason } => write!(f, "{path}: {reason}"),
+            WikiSearchError::CorruptGraph(reason) => write!(f, "corrupt graph: {reason}"),
+            WikiSearchError::VersionMismatch {
+                path,
+                found,
+                expected,
+            } => write!(
+                f,
+                "{path}: format version {found} but this build reads version {expected}, \
+                 rebuild the graph"
+            ),
+            WikiSearchError::UnknownNode(unresolved) => write!(f, "{unresolved}"),
+            WikiSearchError::EmptyGraph => write!(f, "no article is part of the graph"),
+            WikiSearchError::InvalidInput(msg) | WikiSearchError::Unsupported(msg) => {
+                write!(f, "{msg}")
+            }
+            WikiSearchError::Io(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for WikiSearchError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            WikiSearchError::UnknownNode(unresolved) => Some(unresolved),
+            WikiSearchError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for WikiSearchError {
+    fn from(e: std::io::Error) -> Self {
+        WikiSearchError::Io(e)
+    }
+}
+
+impl From<Unresolved> for WikiSearchError {
+    fn from(unresolved: Unresolved) -> Self {
+        WikiSearchError::UnknownNode(unresolved)
+    }
+}
@@ -0,0 +1,261 @@
+use scraper::{ElementRef, Html, Selector};
+use serde::{Deserialize, Serialize};
+
+use crate::filter::LinkFilter;
+
+/// The part of an article a link was found in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum LinkRegion {
+    /// Before the first heading
+    Lead,
+    /// Running text under the `n`th `h2` heading, from 1
+    Section(u8),
+    Infobox,
+    Table,
+    List,
+    Navbox,
+    /// References, notes, "See also" and external links
+    Footer,
+}
+
+// sections past this share a region, articles rarely get anywhere near it
+const MAX_SECTION: u8 = u8::MAX - SECTION_BASE;
+const SECTION_BASE: u8 = 16;
+
+/// Names of the kinds of region, as `LinkRegion::kind` gives them
+pub const REGION_KINDS: &[&str] = &[
+    "lead", "section", "infobox", "table", "list", "navbox", "footer",
+];
+
+impl LinkRegion {
+    /// The region without its section number, one of `REGION_KINDS`
+    pub fn kind(self) -> &'static str {
+        match self {
+            LinkRegion::Lead => "lead",
+            LinkRegion::Section(_) => "section",
+            LinkRegion::Infobox => "infobox",
+            LinkRegion::Table => "table",
+            LinkRegion::List => "list",
+            LinkRegion::Navbox => "navbox",
+            LinkRegion::Footer => "footer",
+        }
+    }
+
+    /// One byte per edge in the graph file
+    pub fn to_byte(self) -> u8 {
+        match self {
+            LinkRegion::Lead => 0,
+            LinkRegion::Infobox => 1,
+            LinkRegion::Table => 2,
+            LinkRegion::List => 3,
+            LinkRegion::Navbox => 4,
+            LinkRegion::Footer => 5,
+            LinkRegion::Section(n) => SECTION_BASE + n.min(MAX_SECTION),
+        }
+    }
+
+    pub fn from_byte(byte: u8) -> Self {
+        match byte {
+            1 => LinkRegion::Infobox,
+            2 => LinkRegion::Table,
+            3 => LinkRegion::List,
+            4 => LinkRegion::Navbox,
+            5 => LinkRegion::Footer,
+            n if n >= SECTION_BASE => LinkRegion::Section(n - SECTION_BASE),
+            _ => LinkRegion::Lead,
+        }
+    }
+}
+
+impl std::fmt::Display for LinkRegion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LinkRegion::Section(n) => write!(f, "section {n}"),
+            region => write!(f, "{}", region.kind()),
+        }
+    }
+}
+
+/// What a link says and the sentence around it, shown to explain a path
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct LinkText {
+    pub anchor: String,
+    pub context: String,
+}
+
+/// A link as it appears in an article, before it is filtered or interned
+#[derive(Debug, Clone)]
+pub struct RawLink<'a> {
+    pub href: &'a str,
+    /// Number of `h2` headings before the link
+    pub section: usize,
+    pub region: LinkRegion,
+    /// Only captured when asked for, it costs a walk over the surrounding block
+    pub text: Option<LinkText>,
+}
+
+/// Elements whose text is read to find the sentence a link sits in
+const TEXT_BLOCKS: &[&str] = &[
+    "p",
+    "li",
+    "dd",
+    "dt",
+    "td",
+    "th",
+    "caption",
+    "figcaption",
+    "blockquote",
+];
+// characters of context kept on either side of the anchor
+const CONTEXT_CHARS: usize = 160;
+
+fn squash_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// The anchor text of `link` and the sentence of its enclosing block it appears in
+fn link_text(link: ElementRef) -> LinkText {
+    let anchor = squash_whitespace(&link.text().collect::<String>());
+    let Some(block) = link
+        .ancestors()
+        .filter_map(ElementRef::wrap)
+        .find(|a| TEXT_BLOCKS.contains(&a.value().name()))
+    else {
+        return LinkText {
+            context: anchor.clone(),
+            anchor,
+        };
+    };
+
+    let (mut before, mut after) = (String::new(), String::new());
+    let mut seen_link = false;
+    for node in block.descendants() {
+        if node.id() == link.id() {
+            seen_link = true;
+        }
+        let Some(text) = node.value().as_text() else {
+            continue;
+        };
+        if node.ancestors().any(|a| a.id() == link.id()) {
+            continue;
+        }
+        match seen_link {
+            false => before.push_str(text),
+            true => after.push_str(text),
+        }
+    }
+
+    // from the end of the previous sentence to the end of this one
+    let before = squash_whitespace(&before);
+    let after = squash_whitespace(&after);
+    let mut start = before
+        .rfind(". ")
+        .map_or(0, |i| i + 2)
+        .max(before.len().saturating_sub(CONTEXT_CHARS));
+    while !before.is_char_boundary(start) {
+        start += 1;
+    }
+    let mut end = after
+        .find(". ")
+        .map_or(after.len(), |i| i + 1)
+        .min(CONTEXT_CHARS);
+    while !after.is_char_boundary(end) {
+        end -= 1;
+    }
+    let (before, after) = (&before[start..], &after[..end]);
+    let before_gap = match before.is_empty() {
+        true => "",
+        false => " ",
+    };
+    let after_gap = match after.is_empty() || after.starts_with([',', '.', ';', ':', ')']) {
+        true => "",
+        false => " ",
+    };
+    LinkText {
+        context: format!("{before}{before_gap}{anchor}{after_gap}{after}"),
+        anchor,
+    }
+}
+
+/// Headings of the sections that only hold references and pointers elsewhere
+const FOOTER_HEADINGS: &[&str] = &[
+    "references",
+    "notes",
+    "footnotes",
+    "sources",
+    "bibliography",
+    "see also",
+    "external links",
+    "further reading",
+    "other websites",
+];
+
+const FOOTER_CLASSES: &[&str] = &["reflist", "references", "refbegin", "mw-references-wrap"];
+const NAVBOX_CLASSES: &[&str] = &["navbox", "vertical-navbox", "navbox-inner", "metadata"];
+
+/// The region a link belongs to from its closest telling ancestor
+fn classify(link: ElementRef, section: usize, in_footer: bool) -> LinkRegion {
+    let mut region = None;
+    for ancestor in link.ancestors().filter_map(ElementRef::wrap) {
+        let element = ancestor.value();
+        let has_class = |classes: &[&str]| element.classes().any(|c| classes.contains(&c));
+        // boxes win over anything they contain, the nearest table or list otherwise
+        if has_class(NAVBOX_CLASSES) || element.attr("role") == Some("navigation") {
+            return LinkRegion::Navbox;
+        }
+        if element.classes().any(|c| c.starts_with("infobox")) {
+            return LinkRegion::Infobox;
+        }
+        if has_class(FOOTER_CLASSES) {
+            return LinkRegion::Footer;
+        }
+        region = region.or(match element.name() {
+            "table" => Some(LinkRegion::Table),
+            "ul" | "ol" | "dl" => Some(LinkRegion::List),
+            _ => None,
+        });
+    }
+    match (in_footer, region, section) {
+        (true, _, _) => LinkRegion::Footer,
+        (false, Some(region), _) => region,
+        (false, None, 0) => LinkRegion::Lead,
+        (false, None, n) => LinkRegion::Section(n.min(MAX_SECTION as usize) as u8),
+    }
+}
+
+/// Every `a[href]` of `doc` that `filter` lets through, by the elements around it and the
+/// region it is in, in document order and tagged with that region.
+pub fn extract_links<'a>(
+    doc: &'a Html,
+    filter: &LinkFilter,
+    capture_text: bool,
+) -> Vec<RawLink<'a>> {
+    let excluded = filter.excluded_subtrees(doc);
+    // headings come through in document order too, so each link knows its section
+    let selector = Selector::parse("h2, a[href]").unwrap();
+    let mut section = 0;
+    let mut in_footer = false;
+    doc.select(&selector)
+        .filter_map(|e| {
+            if e.value().name() == "h2" {
+                section += 1;
+                let heading = e.text().collect::<String>().trim().to_lowercase();
+                in_footer = FOOTER_HEADINGS.contains(&heading.as_str());
+                return None;
+            }
+            if !filter.allows(e, &excluded) {
+                return None;
+            }
+            let region = classify(e, section, in_footer);
+            if !filter.allows_region(region) {
+                return None;
+            }
+            Some(RawLink {
+                href: e.attr("href")?,
+                section,
+                region,
+                text: capture_text.then(|| link_text(e)),
+            })
+        })
+        .collect()
+}
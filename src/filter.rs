@@ -0,0 +1,270 @@
+use ego_tree::NodeId;
+use scraper::{ElementRef, Html, Selector};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use crate::error::{Result, WikiSearchError};
+use crate::extract::{LinkRegion, REGION_KINDS};
+
+/// Entries holding page resources rather than articles
+const BASE_HREFS: &[&str] = &["_assets*", "_mw_*", "_res_*", "-/*"];
+
+const WIKIPEDIA_SELECTORS: &[&str] = &[
+    ".navbox",
+    ".vertical-navbox",
+    ".navbox-styles",
+    ".metadata",
+    ".hatnote",
+    ".reflist",
+    ".references",
+    ".mw-references-wrap",
+    "#toc",
+];
+const WIKIPEDIA_HREFS: &[&str] = &[
+    "File:*",
+    "Special:*",
+    "Help:*",
+    "Template:*",
+    "Template_talk:*",
+    "Category:*",
+    "Wikipedia:*",
+    "Portal:*",
+    "Talk:*",
+];
+
+const WIKTIONARY_SELECTORS: &[&str] = &[
+    ".NavFrame",
+    ".translations",
+    ".references",
+    ".mw-references-wrap",
+    "#toc",
+];
+const WIKTIONARY_HREFS: &[&str] = &[
+    "Appendix:*",
+    "Wiktionary:*",
+    "Rhymes:*",
+    "Index:*",
+    "Category:*",
+    "Template:*",
+    "Citations:*",
+];
+
+// the medicine selection keeps the Wikipedia layout, plus sidebars of medical templates
+const MEDICINE_SELECTORS: &[&str] = &[".sidebar", ".medical-resources"];
+
+/// Built-in filters for the ZIM flavours we build graphs from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum FilterPreset {
+    /// Only drop links that cannot point at an article
+    None,
+    Wikipedia,
+    Wiktionary,
+    Medicine,
+}
+
+/// Which links to leave out of the graph, stored in the graph header
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct FilterConfig {
+    pub name: String,
+    /// Links inside an element matching any of these CSS selectors are dropped
+    pub exclude_selectors: Vec<String>,
+    /// Links whose normalized path matches any of these globs are dropped, `*` matches any
+    /// run of characters and `#` any digit
+    pub exclude_hrefs: Vec<String>,
+    /// Links in regions of these kinds are dropped, see `LinkRegion::kind`
+    pub exclude_regions: Vec<String>,
+}
+
+fn owned(patterns: &[&[&str]]) -> Vec<String> {
+    patterns.concat().into_iter().map(String::from).collect()
+}
+
+impl FilterPreset {
+    pub fn config(self) -> FilterConfig {
+        let (name, selectors, hrefs): (&str, &[&[&str]], &[&[&str]]) = match self {
+            FilterPreset::None => ("none", &[], &[BASE_HREFS]),
+            FilterPreset::Wikipedia => (
+                "wikipedia",
+                &[WIKIPEDIA_SELECTORS],
+                &[BASE_HREFS, WIKIPEDIA_HREFS],
+            ),
+            FilterPreset::Wiktionary => (
+                "wiktionary",
+                &[WIKTIONARY_SELECTORS],
+                &[BASE_HREFS, WIKTIONARY_HREFS],
+            ),
+            FilterPreset::Medicine => (
+                "medicine",
+                &[WIKIPEDIA_SELECTORS, MEDICINE_SELECTORS],
+                &[BASE_HREFS, WIKIPEDIA_HREFS],
+            ),
+        };
+        FilterConfig {
+            name: name.to_string(),
+            exclude_selectors: owned(selectors),
+            exclude_hrefs: owned(hrefs),
+            exclude_regions: Vec::new(),
+        }
+    }
+}
+
+impl FilterConfig {
+    /// Adds exclusions on top of a preset, the name records that it was changed
+    pub fn extend(&mut self, selectors: &[String], hrefs: &[String], regions: &[String]) {
+        if selectors.is_empty() && hrefs.is_empty() && regions.is_empty() {
+            return;
+        }
+        self.name.push('+');
+        self.exclude_selectors.extend_from_slice(selectors);
+        self.exclude_hrefs.extend_from_slice(hrefs);
+        self.exclude_regions.extend_from_slice(regions);
+    }
+}
+
+/// Whether `text` matches `pattern`, where `*` matches any run of characters, `?` any one
+/// and `#` any digit
+pub(crate) fn glob_match(pattern: &str, text: &str) -> bool {
+    let (pattern, text): (Vec<char>, Vec<char>) =
+        (pattern.chars().collect(), text.chars().collect());
+    let (mut p, mut t) = (0, 0);
+    // where the last `*` was and the text position it is currently standing in for
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] || (c == '#' && text[t].is_ascii_digit()) => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    star = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// A compiled `FilterConfig`
+#[derive(Debug)]
+pub struct LinkFilter {
+    config: FilterConfig,
+    // every selector joined into one, `None` when there are none
+    selector: Option<Selector>,
+}
+
+impl LinkFilter {
+    pub fn new(config: FilterConfig) -> Result<Self> {
+        if let Some(region) = config
+            .exclude_regions
+            .iter()
+            .find(|region| !REGION_KINDS.contains(&region.as_str()))
+        {
+            return Err(WikiSearchError::InvalidInput(format!(
+                "unknown region {region:?}, expected one of {}",
+                REGION_KINDS.join(", ")
+            )));
+        }
+        let selector = match config.exclude_selectors.is_empty() {
+            true => None,
+            false => {
+                let joined = config.exclude_selectors.join(", ");
+                Some(Selector::parse(&joined).map_err(|e| {
+                    WikiSearchError::InvalidInput(format!(
+                        "invalid exclude selector {joined:?}: {e}"
+                    ))
+                })?)
+            }
+        };
+        Ok(LinkFilter { config, selector })
+    }
+
+    pub fn config(&self) -> &FilterConfig {
+        &self.config
+    }
+
+    pub fn allows_href(&self, href: &str) -> bool {
+        !self
+            .config
+            .exclude_hrefs
+            .iter()
+            .any(|pattern| glob_match(pattern, href))
+    }
+
+    pub fn allows_region(&self, region: LinkRegion) -> bool {
+        !self
+            .config
+            .exclude_regions
+            .iter()
+            .any(|kind| kind == region.kind())
+    }
+
+    /// Roots of the subtrees of `doc` whose links are all dropped
+    pub fn excluded_subtrees(&self, doc: &Html) -> HashSet<NodeId> {
+        match &self.selector {
+            Some(selector) => doc.select(selector).map(|e| e.id()).collect(),
+            None => HashSet::new(),
+        }
+    }
+
+    /// Whether `link` sits in none of `excluded` (from `excluded_subtrees`), its href is only
+    /// checked with `allows_href` once normalized
+    pub fn allows(&self, link: ElementRef, excluded: &HashSet<NodeId>) -> bool {
+        !std::iter::once(link.id())
+            .chain(link.ancestors().map(|node| node.id()))
+            .any(|id| excluded.contains(&id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_wildcards() {
+        assert!(glob_match("List_of_*", "List_of_rivers"));
+        assert!(glob_match("List_of_*", "List_of_"));
+        assert!(!glob_match("List_of_*", "Lists_of_rivers"));
+        assert!(glob_match("*_(disambiguation)", "Mercury_(disambiguation)"));
+        assert!(glob_match("a*b*c", "axxbyyc"));
+        assert!(!glob_match("a*b*c", "axxbyy"));
+        assert!(glob_match("Caf?", "Café"));
+        assert!(!glob_match("Caf?", "Caf"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("", "x"));
+    }
+
+    #[test]
+    fn regions_are_excluded_by_kind() {
+        let mut config = FilterPreset::None.config();
+        config.extend(&[], &[], &["navbox".to_string(), "section".to_string()]);
+        assert_eq!(config.name, "none+");
+        let filter = LinkFilter::new(config).unwrap();
+        assert!(!filter.allows_region(LinkRegion::Navbox));
+        assert!(!filter.allows_region(LinkRegion::Section(3)));
+        assert!(filter.allows_region(LinkRegion::Lead));
+        assert!(filter.allows_region(LinkRegion::Footer));
+
+        let mut config = FilterPreset::None.config();
+        config.extend(&[], &[], &["sidebar".to_string()]);
+        assert!(LinkFilter::new(config).is_err());
+    }
+
+    #[test]
+    fn glob_digits() {
+        assert!(glob_match("####", "1999"));
+        assert!(!glob_match("####", "199"));
+        assert!(!glob_match("####", "19999"));
+        assert!(!glob_match("####", "199a"));
+        assert!(glob_match("#*_BC", "44_BC"));
+        assert!(glob_match("1#", "1#"));
+    }
+}
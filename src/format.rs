@@ -0,0 +1,408 @@
+use memmap2::Mmap;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use zim_rs::archive::Archive;
+
+use crate::error::{Result, WikiSearchError};
+use crate::filter::FilterConfig;
+
+const MAGIC: &[u8; 8] = b"WIKIGRPH";
+/// Bump whenever the layout of any file written here changes
+pub const FORMAT_VERSION: u32 = 8;
+// sections of mapped files start on this boundary so they can be viewed as u64s
+const SECTION_ALIGN: usize = 8;
+
+/// What a file written here holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum FileKind {
+    Graph,
+    Landmarks,
+    PageRank,
+    LinkText,
+    Checkpoint,
+}
+
+/// Identity of the ZIM archive a graph was built from
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SourceArchive {
+    pub filename: String,
+    pub uuid: String,
+    pub checksum: Option<String>,
+    pub entry_count: u32,
+}
+
+impl SourceArchive {
+    pub fn of(a: &Archive) -> Self {
+        SourceArchive {
+            filename: a.get_filename(),
+            uuid: a
+                .get_uuid()
+                .ok()
+                .and_then(|uuid| (&uuid).try_into().ok())
+                .unwrap_or_default(),
+            checksum: a.has_checksum().then(|| a.get_checksum().ok()).flatten(),
+            entry_count: a.get_all_entrycount(),
+        }
+    }
+}
+
+/// Something off about a graph or the files next to it that does not stop it from loading
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadWarning {
+    /// The archive has the uuid the graph was built from but a different checksum
+    ChecksumMismatch { filename: String },
+    /// The side file at `path` was computed for a different graph and was left out
+    StaleSideFile { path: String },
+}
+
+impl std::fmt::Display for LoadWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoadWarning::ChecksumMismatch { filename } => write!(
+                f,
+                "{filename} has the uuid the graph was built from but a different checksum"
+            ),
+            LoadWarning::StaleSideFile { path } => {
+                write!(f, "ignoring {path}, it was computed for a different graph")
+            }
+        }
+    }
+}
+
+/// Describes how a graph was built, stored at the start of every file
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct GraphHeader {
+    pub source: SourceArchive,
+    /// Name of the scheme that turned link positions into edge weights
+    pub weighting: String,
+    /// Links that were left out of the graph
+    pub link_filter: FilterConfig,
+    /// Seconds since the unix epoch
+    pub built_at: u64,
+    pub builder_version: String,
+}
+
+impl GraphHeader {
+    pub fn new(a: &Archive, weighting: &str, link_filter: &FilterConfig) -> Self {
+        GraphHeader {
+            source: SourceArchive::of(a),
+            weighting: weighting.to_string(),
+            link_filter: link_filter.clone(),
+            built_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+            builder_version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
+
+    /// Header of the graph saved at `graph_path`, without loading the graph
+    pub fn read(graph_path: &str) -> Result<Self> {
+        map_sections(graph_path, FileKind::Graph).map(|(header, _, _)| header)
+    }
+
+    /// Opens the archive at `zim_path` and checks it is the one the graph was built from
+    pub fn check_archive(&self, zim_path: &str) -> Result<Vec<LoadWarning>> {
+        let mut warnings = Vec::new();
+        self.check_source(&open_archive(zim_path)?, &mut warnings)?;
+        Ok(warnings)
+    }
+
+    /// Refuses archives other than the one the graph was built from, and warns about one
+    /// that only looks like it
+    pub fn check_source(&self, a: &Archive, warnings: &mut Vec<LoadWarning>) -> Result<()> {
+        let opened = SourceArchive::of(a);
+        if opened.uuid != self.source.uuid {
+            return Err(WikiSearchError::ArchiveMismatch {
+                expected: format!("{} (uuid {})", self.source.filename, self.source.uuid),
+                found: format!("{} (uuid {})", opened.filename, opened.uuid),
+            });
+        }
+        if opened.checksum != self.source.checksum {
+            warnings.push(LoadWarning::ChecksumMismatch {
+                filename: opened.filename,
+            });
+        }
+        Ok(())
+    }
+}
+
+pub fn open_archive(path: &str) -> Result<Archive> {
+    Archive::new(path).map_err(|()| WikiSearchError::ArchiveOpen {
+        path: path.to_string(),
+    })
+}
+
+fn encode_error(e: bincode::error::EncodeError) -> WikiSearchError {
+    WikiSearchError::Io(std::io::Error::other(e.to_string()))
+}
+
+fn encode_prefix(kind: FileKind, header: &GraphHeader) -> Result<Vec<u8>> {
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    bincode::serde::encode_into_std_write((kind, header), &mut bytes, bincode::config::standard())
+        .map_err(encode_error)?;
+    Ok(bytes)
+}
+
+/// Checks the magic, version and kind, returning the header and where the prefix ends
+fn decode_prefix(path: &str, bytes: &[u8], kind: FileKind) -> Result<(GraphHeader, usize)> {
+    if bytes.len() < MAGIC.len() + 4 || &bytes[..MAGIC.len()] != MAGIC {
+        return Err(WikiSearchError::corrupt_file(
+            path,
+            "not a wiki-search graph file",
+        ));
+    }
+    let version = u32::from_le_bytes(bytes[MAGIC.len()..MAGIC.len() + 4].try_into().unwrap());
+    if version != FORMAT_VERSION {
+        return Err(WikiSearchError::VersionMismatch {
+            path: path.to_string(),
+            found: version,
+            expected: FORMAT_VERSION,
+        });
+    }
+
+    let start = MAGIC.len() + 4;
+    let ((file_kind, header), read): ((FileKind, GraphHeader), usize) =
+        bincode::serde::decode_from_slice(&bytes[start..], bincode::config::standard())
+            .map_err(|e| WikiSearchError::corrupt_file(path, format!("corrupt header ({e})")))?;
+    if file_kind != kind {
+        return Err(WikiSearchError::corrupt_file(
+            path,
+            format!("holds {file_kind:?} data, expected {kind:?}"),
+        ));
+    }
+    Ok((header, start + read))
+}
+
+/// Writes `path` through `{path}.partial`, renamed over `path` once complete. Processes
+/// that still have the old file mapped keep reading it, and a failed write leaves it alone.
+fn replace_file(path: &str, write: impl FnOnce(&mut BufWriter<File>) -> Result<()>) -> Result<()> {
+    let partial = format!("{path}.partial");
+    let mut out = BufWriter::new(File::create(&partial)?);
+    write(&mut out)?;
+    out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+    Ok(std::fs::rename(&partial, path)?)
+}
+
+/// Encodes `payload` after its length, so a segment cut short by a crash can be told apart
+fn encode_segment<T: Serialize>(payload: &T) -> Result<Vec<u8>> {
+    let mut bytes = vec![0; 8];
+    bincode::serde::encode_into_std_write(payload, &mut bytes, bincode::config::standard())
+        .map_err(encode_error)?;
+    let len = (bytes.len() - 8) as u64;
+    bytes[..8].copy_from_slice(&len.to_le_bytes());
+    Ok(bytes)
+}
+
+/// Starts `path` over with the usual prefix and `payload` as its first segment, later ones
+/// are added with `append_segment`. Returns the length of the file.
+pub fn write_segments<T: Serialize>(
+    path: &str,
+    kind: FileKind,
+    header: &GraphHeader,
+    payload: &T,
+) -> Result<u64> {
+    let mut bytes = encode_prefix(kind, header)?;
+    bytes.extend(encode_segment(payload)?);
+    replace_file(path, |out| Ok(out.write_all(&bytes)?))?;
+    Ok(bytes.len() as u64)
+}
+
+/// Adds `payload` after the first `len` bytes of a file started by `write_segments`,
+/// dropping anything a failed append left behind them. Returns the new length.
+pub fn append_segment<T: Serialize>(path: &str, len: u64, payload: &T) -> Result<u64> {
+    let segment = encode_segment(payload)?;
+    let mut file = OpenOptions::new().write(true).open(path)?;
+    file.set_len(len)?;
+    file.seek(SeekFrom::Start(len))?;
+    file.write_all(&segment)?;
+    file.sync_data()?;
+    Ok(len + segment.len() as u64)
+}
+
+/// Reads every segment of a file written by `write_segments` and `append_segment`, and
+/// the length they take up. A last segment cut short by a crash is left out.
+pub fn read_segments<T: DeserializeOwned>(
+    path: &str,
+    kind: FileKind,
+) -> Result<(GraphHeader, Vec<T>, u64)> {
+    let bytes = std::fs::read(path)?;
+    let (header, mut start) = decode_prefix(path, &bytes, kind)?;
+    let mut segments = Vec::new();
+    while let Some(len) = bytes.get(start..start + 8) {
+        let len = u64::from_le_bytes(len.try_into().unwrap()) as usize;
+        let Some(segment) = bytes[start + 8..].get(..len) else {
+            break;
+        };
+        let (payload, _): (T, usize) =
+            bincode::serde::decode_from_slice(segment, bincode::config::standard())
+                .map_err(|e| WikiSearchError::corrupt_file(path, format!("corrupt data ({e})")))?;
+        segments.push(payload);
+        start += 8 + len;
+    }
+    Ok((header, segments, start as u64))
+}
+
+fn native_is_little_endian() -> Result<()> {
+    match cfg!(target_endian = "little") {
+        true => Ok(()),
+        false => Err(WikiSearchError::Unsupported(
+            "mapped graph files are little-endian only".to_string(),
+        )),
+    }
+}
+
+fn padding(len: usize) -> usize {
+    len.next_multiple_of(SECTION_ALIGN) - len
+}
+
+/// Writes the usual prefix, a table of `(offset, length)` pairs and then every section
+/// in native (little-endian) byte order, each aligned so it can be used in place once mapped.
+pub fn write_sections(
+    path: &str,
+    kind: FileKind,
+    header: &GraphHeader,
+    sections: &[&[u8]],
+) -> Result<()> {
+    native_is_little_endian()?;
+    let mut prefix = encode_prefix(kind, header)?;
+    prefix.resize(prefix.len() + padding(prefix.len()), 0);
+
+    let table_len = 8 + 16 * sections.len();
+    let mut table = Vec::with_capacity(table_len);
+    table.extend_from_slice(&(sections.len() as u64).to_le_bytes());
+    let mut offset = prefix.len() + table_len;
+    for section in sections {
+        table.extend_from_slice(&(offset as u64).to_le_bytes());
+        table.extend_from_slice(&(section.len() as u64).to_le_bytes());
+        offset += section.len() + padding(section.len());
+    }
+
+    // never truncate `path` in place, other processes (or `sections`) may be mapping it
+    replace_file(path, |out| {
+        out.write_all(&prefix)?;
+        out.write_all(&table)?;
+        for section in sections {
+            out.write_all(section)?;
+            out.write_all(&[0; SECTION_ALIGN][..padding(section.len())])?;
+        }
+        Ok(())
+    })
+}
+
+/// Maps a file written by `write_sections`, returning the byte range of every section.
+///
+/// The file must not be modified while it is mapped.
+pub fn map_sections(
+    path: &str,
+    kind: FileKind,
+) -> Result<(GraphHeader, Arc<Mmap>, Vec<Range<usize>>)> {
+    native_is_little_endian()?;
+    let file = File::open(path)?;
+    // SAFETY: graph files are replaced by renaming a new file over them, never written in
+    // place, so the mapped file does not change
+    let map = Arc::new(unsafe { Mmap::map(&file)? });
+    let (header, prefix_len) = decode_prefix(path, &map, kind)?;
+
+    let truncated = || WikiSearchError::corrupt_file(path, "truncated");
+    let read_u64 = |at: usize| -> Result<usize> {
+        let bytes = map.get(at..at + 8).ok_or_else(truncated)?;
+        Ok(u64::from_le_bytes(bytes.try_into().unwrap()) as usize)
+    };
+    let table = prefix_len + padding(prefix_len);
+    let count = read_u64(table)?;
+    let mut sections = Vec::with_capacity(count.min(64));
+    for i in 0..count {
+        let offset = read_u64(table + 8 + 16 * i)?;
+        let len = read_u64(table + 16 + 16 * i)?;
+        if offset.checked_add(len).is_none_or(|end| end > map.len()) {
+            return Err(truncated());
+        }
+        sections.push(offset..offset + len);
+    }
+    Ok((header, map, sections))
+}
+
+/// A mapped file and the byte range of each of its sections
+pub type MappedSections = (Arc<Mmap>, Vec<Range<usize>>);
+
+/// Maps an optional file derived from a graph, skipping it when it is missing or was
+/// computed for a graph other than the one described by `graph_header`.
+pub fn map_side_file(
+    path: &str,
+    kind: FileKind,
+    graph_header: &GraphHeader,
+    warnings: &mut Vec<LoadWarning>,
+) -> Result<Option<MappedSections>> {
+    match map_sections(path, kind) {
+        Ok((header, map, sections)) if header == *graph_header => Ok(Some((map, sections))),
+        Ok(_) => {
+            warnings.push(LoadWarning::StaleSideFile {
+                path: path.to_string(),
+            });
+            Ok(None)
+        }
+        Err(WikiSearchError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::FilterPreset;
+
+    fn header() -> GraphHeader {
+        GraphHeader {
+            source: SourceArchive {
+                filename: "test.zim".to_string(),
+                uuid: "0".to_string(),
+                checksum: None,
+                entry_count: 0,
+            },
+            weighting: "linear-position".to_string(),
+            link_filter: FilterPreset::None.config(),
+            built_at: 0,
+            builder_version: String::new(),
+        }
+    }
+
+    #[test]
+    fn segments_are_appended_and_a_cut_short_one_is_dropped() {
+        let path =
+            std::env::temp_dir().join(format!("wiki-search-{}-segments", std::process::id()));
+        let path = path.to_str().unwrap();
+        let kind = FileKind::Checkpoint;
+        let mut len = write_segments(path, kind, &header(), &vec![1_u32, 2]).unwrap();
+        len = append_segment(path, len, &vec![3_u32]).unwrap();
+        let full = append_segment(path, len, &vec![4_u32, 5, 6]).unwrap();
+
+        let (read_header, segments, read_len) = read_segments::<Vec<u32>>(path, kind).unwrap();
+        assert_eq!(read_header, header());
+        assert_eq!(segments, vec![vec![1, 2], vec![3], vec![4, 5, 6]]);
+        assert_eq!(read_len, full);
+
+        // a crash part way through the last append
+        OpenOptions::new()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_len(full - 2)
+            .unwrap();
+        let (_, segments, read_len) = read_segments::<Vec<u32>>(path, kind).unwrap();
+        assert_eq!(segments, vec![vec![1, 2], vec![3]]);
+        assert_eq!(read_len, len);
+
+        // the next append goes over what was cut short
+        let full = append_segment(path, read_len, &vec![7_u32]).unwrap();
+        let (_, segments, read_len) = read_segments::<Vec<u32>>(path, kind).unwrap();
+        assert_eq!(segments, vec![vec![1, 2], vec![3], vec![7]]);
+        assert_eq!(read_len, full);
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,216 @@
+use percent_encoding::percent_decode_str;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// hrefs starting with one of these leave the archive
+const EXTERNAL_SCHEMES: &[&str] = &[
+    "http:",
+    "https:",
+    "ftp:",
+    "mailto:",
+    "javascript:",
+    "data:",
+    "geo:",
+    "tel:",
+    "//",
+];
+
+/// Why an href did not become an edge
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dropped {
+    External,
+    /// Only a fragment or query, or a path back to the page itself
+    SelfLink,
+    /// Climbs above the root of the archive with `..`
+    OutsideArchive,
+    /// Points outside the article namespace of an old-style archive
+    Namespace,
+    /// Excluded by an href pattern of the link filter
+    Filtered,
+    /// Well formed, but the archive has no entry at that path
+    Missing,
+}
+
+/// Resolves `href` found in the entry at `entry_path` to the path of the entry it points at.
+///
+/// Fragments and queries are stripped and the rest percent-decoded, then resolved like a
+/// relative URL against the directory of `entry_path`. Archives without the new namespace
+/// scheme keep articles under `A/`, anything else there is not an article.
+pub fn normalize(
+    href: &str,
+    entry_path: &str,
+    new_namespace_scheme: bool,
+) -> Result<String, Dropped> {
+    let lower = href.trim_start().to_ascii_lowercase();
+    if EXTERNAL_SCHEMES
+        .iter()
+        .any(|scheme| lower.starts_with(scheme))
+    {
+        return Err(Dropped::External);
+    }
+    let href = href.trim();
+    let href = href.split_once('#').map_or(href, |(path, _)| path);
+    let href = href.split_once('?').map_or(href, |(path, _)| path);
+    if href.is_empty() {
+        return Err(Dropped::SelfLink);
+    }
+    let href = percent_decode_str(href).decode_utf8_lossy();
+
+    let mut segments: Vec<&str> = match href.strip_prefix('/') {
+        Some(_) => Vec::new(),
+        // the entry's own name is not part of its directory
+        None => entry_path
+            .rsplit_once('/')
+            .map_or(Vec::new(), |(dir, _)| dir.split('/').collect()),
+    };
+    for segment in href.trim_start_matches('/').split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop().ok_or(Dropped::OutsideArchive)?;
+            }
+            segment => segments.push(segment),
+        }
+    }
+    let path = segments.join("/");
+
+    if path.is_empty() || path == entry_path {
+        return Err(Dropped::SelfLink);
+    }
+    if !new_namespace_scheme && !path.starts_with("A/") {
+        return Err(Dropped::Namespace);
+    }
+    Ok(path)
+}
+
+/// `path` without the `A/` namespace old-style archives keep their articles under
+pub fn article_name(path: &str, new_namespace_scheme: bool) -> &str {
+    match new_namespace_scheme {
+        true => path,
+        false => path.strip_prefix("A/").unwrap_or(path),
+    }
+}
+
+/// What happened to the hrefs seen while building, safe to update from every build thread
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct HrefStats {
+    pub seen: AtomicUsize,
+    /// Kept, but under a different path than the href spelled out
+    pub rewritten: AtomicUsize,
+    pub external: AtomicUsize,
+    pub self_links: AtomicUsize,
+    pub outside_archive: AtomicUsize,
+    pub namespace: AtomicUsize,
+    pub filtered: AtomicUsize,
+    pub missing: AtomicUsize,
+}
+
+impl HrefStats {
+    pub fn record_kept(&self, rewritten: bool) {
+        self.seen.fetch_add(1, Ordering::Relaxed);
+        if rewritten {
+            self.rewritten.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn record_drop(&self, reason: Dropped) {
+        self.seen.fetch_add(1, Ordering::Relaxed);
+        let counter = match reason {
+            Dropped::External => &self.external,
+            Dropped::SelfLink => &self.self_links,
+            Dropped::OutsideArchive => &self.outside_archive,
+            Dropped::Namespace => &self.namespace,
+            Dropped::Filtered => &self.filtered,
+            Dropped::Missing => &self.missing,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl std::fmt::Display for HrefStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let get = |counter: &AtomicUsize| counter.load(Ordering::Relaxed);
+        write!(
+            f,
+            "{} hrefs: {} rewritten, dropped {} external, {} self links, {} outside the archive, \
+             {} outside the article namespace, {} filtered, {} missing",
+            get(&self.seen),
+            get(&self.rewritten),
+            get(&self.external),
+            get(&self.self_links),
+            get(&self.outside_archive),
+            get(&self.namespace),
+            get(&self.filtered),
+            get(&self.missing),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_relative_hrefs() {
+        assert_eq!(normalize("Paris", "Lyon", true), Ok("Paris".to_string()));
+        assert_eq!(
+            normalize("Paris", "A/Lyon", false),
+            Ok("A/Paris".to_string())
+        );
+        assert_eq!(
+            normalize("./Paris", "a/b/Lyon", true),
+            Ok("a/b/Paris".to_string())
+        );
+        assert_eq!(
+            normalize("../c/../Paris", "a/b/Lyon", true),
+            Ok("a/Paris".to_string())
+        );
+        assert_eq!(normalize("/Paris", "a/Lyon", true), Ok("Paris".to_string()));
+    }
+
+    #[test]
+    fn strips_fragments_and_queries_and_decodes() {
+        assert_eq!(
+            normalize("Caf%C3%A9#History", "Lyon", true),
+            Ok("Café".to_string())
+        );
+        assert_eq!(
+            normalize("Paris?action=x#y", "Lyon", true),
+            Ok("Paris".to_string())
+        );
+        assert_eq!(normalize(" Paris ", "Lyon", true), Ok("Paris".to_string()));
+    }
+
+    #[test]
+    fn drops_hrefs_that_are_not_articles() {
+        assert_eq!(
+            normalize("https://x.org", "Lyon", true),
+            Err(Dropped::External)
+        );
+        assert_eq!(
+            normalize("  MailTo:a@b", "Lyon", true),
+            Err(Dropped::External)
+        );
+        assert_eq!(
+            normalize("//x.org/Paris", "Lyon", true),
+            Err(Dropped::External)
+        );
+        assert_eq!(normalize("#top", "Lyon", true), Err(Dropped::SelfLink));
+        assert_eq!(normalize("Lyon#top", "Lyon", true), Err(Dropped::SelfLink));
+        assert_eq!(normalize(".", "Lyon", true), Err(Dropped::SelfLink));
+        assert_eq!(
+            normalize("../Paris", "Lyon", true),
+            Err(Dropped::OutsideArchive)
+        );
+        assert_eq!(
+            normalize("../I/x.png", "A/Lyon", false),
+            Err(Dropped::Namespace)
+        );
+    }
+
+    #[test]
+    fn article_names_drop_the_old_namespace() {
+        assert_eq!(article_name("A/Paris", false), "Paris");
+        assert_eq!(article_name("A/Paris", true), "A/Paris");
+    }
+}
@@ -0,0 +1,187 @@
+use lasso::Spur;
+use rayon::prelude::*;
+use std::sync::OnceLock;
+
+use crate::csr::{CsrGraph, node_id, node_key};
+use crate::error::{Result, WikiSearchError};
+use crate::{WikiGraph, paths};
+
+/// Penalty a page with the highest score gets unless told otherwise, about as much as
+/// following two more links
+pub const DEFAULT_HUB_STRENGTH: f32 = 2.0;
+
+/// What makes a page a hub
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum HubMeasure {
+    /// Links on the page
+    OutDegree,
+    /// Links to the page
+    InDegree,
+    /// PageRank score, needs scores computed or loaded
+    #[cfg_attr(feature = "cli", value(name = "pagerank"))]
+    PageRank,
+}
+
+impl std::str::FromStr for HubMeasure {
+    type Err = WikiSearchError;
+
+    /// Reads the names the command line uses: `out-degree`, `in-degree` or `pagerank`
+    fn from_str(name: &str) -> Result<Self> {
+        match name {
+            "out-degree" => Ok(HubMeasure::OutDegree),
+            "in-degree" => Ok(HubMeasure::InDegree),
+            "pagerank" => Ok(HubMeasure::PageRank),
+            _ => Err(WikiSearchError::InvalidInput(format!(
+                "unknown hub measure `{name}`, expected out-degree, in-degree or pagerank"
+            ))),
+        }
+    }
+}
+
+/// Extra cost of going through a page, on top of the `1 + weight` of the link to it
+#[derive(Debug, Clone, Copy)]
+pub struct HubPenalty {
+    pub measure: HubMeasure,
+    /// Penalty of the page scoring highest, the others get a share proportional to their
+    /// score
+    pub strength: f32,
+}
+
+/// Highest score of every measure, worked out the first time a query penalizes by it
+#[derive(Debug, Default)]
+pub(crate) struct HubMaxima {
+    out_degree: OnceLock<f32>,
+    in_degree: OnceLock<f32>,
+    pagerank: OnceLock<f32>,
+}
+
+impl WikiGraph {
+    /// Score of `node` by `measure` before it is normalized
+    fn hub_score(&self, measure: HubMeasure, node: u32) -> f32 {
+        match measure {
+            HubMeasure::OutDegree => self.graph.out_degree(node) as f32,
+            HubMeasure::InDegree => self.reverse.out_degree(node) as f32,
+            HubMeasure::PageRank => self
+                .pagerank
+                .as_ref()
+                .and_then(|scores| scores.get(node as usize).copied())
+                .unwrap_or(0.0),
+        }
+    }
+
+    /// Highest score of any node by `measure`, computed once until the graph or its
+    /// PageRank scores change
+    fn hub_maximum(&self, measure: HubMeasure) -> Result<f32> {
+        if measure == HubMeasure::PageRank && self.pagerank.is_none() {
+            return Err(WikiSearchError::Unsupported(
+                "no PageRank scores for this graph, run `pagerank` first".to_string(),
+            ));
+        }
+        let cell = match measure {
+            HubMeasure::OutDegree => &self.hub_maxima.out_degree,
+            HubMeasure::InDegree => &self.hub_maxima.in_degree,
+            HubMeasure::PageRank => &self.hub_maxima.pagerank,
+        };
+        Ok(*cell.get_or_init(|| {
+            (0..self.graph.node_count() as u32)
+                .into_par_iter()
+                .map(|node| self.hub_score(measure, node))
+                .reduce(|| 0.0, f32::max)
+        }))
+    }
+
+    /// Like `find_shortest_path`, but every page the path goes through costs `penalty` on
+    /// top of the link to it, steering the path around hubs such as countries and years.
+    ///
+    /// The start and target pay no penalty. Landmarks stay valid lower bounds since
+    /// penalties only make paths longer.
+    pub fn find_shortest_path_penalized(
+        &self,
+        first_link: Spur,
+        target_link: Spur,
+        penalty: HubPenalty,
+    ) -> Result<Option<Vec<Spur>>> {
+        if !(penalty.strength.is_finite() && penalty.strength >= 0.0) {
+            return Err(WikiSearchError::InvalidInput(format!(
+                "hub penalty strength must be a non-negative number, not {}",
+                penalty.strength
+            )));
+        }
+        // scores are divided by the highest so they fall in 0..=1
+        let highest = self.hub_maximum(penalty.measure)?;
+        let scale = match highest > 0.0 {
+            true => penalty.strength / highest,
+            false => 0.0,
+        };
+        let (source, target) = (
+            node_id(self.canonical(first_link)),
+            node_id(self.canonical(target_link)),
+        );
+        let found = penalized_astar(
+            &self.graph,
+            source,
+            target,
+            |node| match &self.landmarks {
+                Some(landmarks) => landmarks.lower_bound(node, target),
+                None => 0.0,
+            },
+            |node| scale * self.hub_score(penalty.measure, node),
+        );
+        Ok(found.map(|(_, path)| path.into_iter().map(node_key).collect()))
+    }
+}
+
+/// A* from `source` to `target` where stepping on any page but the target costs
+/// `penalty(node)` more
+fn penalized_astar(
+    graph: &CsrGraph,
+    source: u32,
+    target: u32,
+    lower_bound: impl Fn(u32) -> f32,
+    penalty: impl Fn(u32) -> f32,
+) -> Option<(f32, Vec<u32>)> {
+    paths::astar(
+        graph,
+        source,
+        target,
+        lower_bound,
+        |node| match node == target {
+            true => 0.0,
+            false => penalty(node),
+        },
+        |_, _| true,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn penalizing_hubs_changes_the_route() {
+        // 0 -> 1 -> 3 is cheapest but 1 is linked from everywhere, 0 -> 2 -> 3 costs a little
+        // more and 2 is linked from 0 only
+        let mut edges = vec![(0, 1, 0.0), (1, 3, 0.0), (0, 2, 0.5), (2, 3, 0.0)];
+        edges.extend((4..10).map(|from| (from, 1, 0.0)));
+        let graph = CsrGraph::from_edges(10, &edges);
+        let reverse = graph.transpose();
+        let highest = (0..10).map(|node| reverse.out_degree(node)).max().unwrap() as f32;
+        let path = |strength: f32| {
+            let penalty = |node| strength * reverse.out_degree(node) as f32 / highest;
+            penalized_astar(&graph, 0, 3, |_| 0.0, penalty).map(|(_, path)| path)
+        };
+
+        assert_eq!(path(0.0), Some(vec![0, 1, 3]));
+        assert_eq!(path(DEFAULT_HUB_STRENGTH), Some(vec![0, 2, 3]));
+        // a weak penalty is not worth the detour
+        assert_eq!(path(0.2), Some(vec![0, 1, 3]));
+    }
+
+    #[test]
+    fn the_target_pays_no_penalty() {
+        let graph = CsrGraph::from_edges(3, &[(0, 1, 0.0), (2, 1, 0.0)]);
+        let found = penalized_astar(&graph, 0, 1, |_| 0.0, |_| 10.0);
+        assert_eq!(found, Some((1.0, vec![0, 1])));
+    }
+}
@@ -0,0 +1,184 @@
+use ordered_float::OrderedFloat;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashSet};
+
+use crate::csr::CsrGraph;
+use crate::paths;
+
+/// Cost of following every link of `path` in `graph`, infinite if one is missing
+pub fn path_distance(graph: &CsrGraph, path: &[u32]) -> f32 {
+    path.windows(2)
+        .map(|hop| {
+            graph
+                .outlinks(hop[0])
+                .find(|&(link, _)| link == hop[1])
+                .map_or(f32::INFINITY, |(_, weight)| weight + 1_f32)
+        })
+        .sum()
+}
+
+/// Up to `k` paths that never visit a node twice, starting with `shortest` and going on
+/// with the next shortest from its first node to its last, found with Yen's algorithm.
+///
+/// Every later path leaves an earlier one at one of its nodes and takes the shortest detour
+/// from there that avoids the links the paths sharing that prefix took next and the nodes
+/// before it. Paths of equal distance come with the fewest hops first.
+///
+/// Detours are searched with A*, `lower_bound(node)` must never overestimate the distance
+/// from `node` to the last node of `shortest` in the full graph.
+pub fn k_shortest_paths(
+    graph: &CsrGraph,
+    shortest: Vec<u32>,
+    k: usize,
+    lower_bound: impl Fn(u32) -> f32,
+) -> Vec<(f32, Vec<u32>)> {
+    let Some(&target) = shortest.last().filter(|_| k > 0) else {
+        return Vec::new();
+    };
+    let mut found = vec![(path_distance(graph, &shortest), shortest)];
+    let mut seen: HashSet<Vec<u32>> = HashSet::from([found[0].1.clone()]);
+    // detours not taken yet, shortest first and the one with fewer hops on a tie
+    let mut candidates = BinaryHeap::new();
+
+    while found.len() < k {
+        let previous = &found[found.len() - 1].1;
+        for i in 0..previous.len() - 1 {
+            let root = &previous[..=i];
+            let edges: HashSet<(u32, u32)> = found
+                .iter()
+                .filter(|(_, path)| path.len() > i + 1 && path[..=i] == *root)
+                .map(|(_, path)| (path[i], path[i + 1]))
+                .collect();
+            let nodes: HashSet<u32> = root[..i].iter().copied().collect();
+            let Some((_, detour)) = paths::astar(
+                graph,
+                previous[i],
+                target,
+                &lower_bound,
+                |_| 0.0,
+                |from, to| !nodes.contains(&to) && !edges.contains(&(from, to)),
+            ) else {
+                continue;
+            };
+
+            let path: Vec<u32> = root[..i].iter().copied().chain(detour).collect();
+            if seen.insert(path.clone()) {
+                let distance = path_distance(graph, &path);
+                candidates.push(Reverse((OrderedFloat(distance), path.len(), path)));
+            }
+        }
+        let Some(Reverse((distance, _, path))) = candidates.pop() else {
+            break;
+        };
+        found.push((distance.0, path));
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::paths::bidirectional_dijkstra;
+
+    /// Every path from `source` to `target` that visits no node twice, shortest first
+    fn all_simple_paths(graph: &CsrGraph, source: u32, target: u32) -> Vec<(f32, Vec<u32>)> {
+        fn extend(graph: &CsrGraph, path: &mut Vec<u32>, target: u32, out: &mut Vec<Vec<u32>>) {
+            let last = path[path.len() - 1];
+            if last == target {
+                out.push(path.clone());
+                return;
+            }
+            for (link, _) in graph.outlinks(last) {
+                if !path.contains(&link) {
+                    path.push(link);
+                    extend(graph, path, target, out);
+                    path.pop();
+                }
+            }
+        }
+        let mut paths = Vec::new();
+        extend(graph, &mut vec![source], target, &mut paths);
+        let mut paths: Vec<_> = paths
+            .into_iter()
+            .map(|path| (path_distance(graph, &path), path))
+            .collect();
+        paths.sort_by(|a, b| a.0.total_cmp(&b.0));
+        paths
+    }
+
+    #[test]
+    fn finds_the_next_shortest_paths_in_order() {
+        //   0 -> 1 -> 3, 0 -> 2 -> 3 and 0 -> 1 -> 2 -> 3
+        let graph = CsrGraph::from_edges(
+            4,
+            &[
+                (0, 1, 0.0),
+                (0, 2, 0.5),
+                (1, 3, 0.0),
+                (1, 2, 0.0),
+                (2, 3, 0.0),
+            ],
+        );
+        let (_, shortest) = bidirectional_dijkstra(&graph, &graph.transpose(), 0, 3).unwrap();
+        let found = k_shortest_paths(&graph, shortest, 5, |_| 0.0);
+        assert_eq!(
+            found,
+            vec![
+                (2.0, vec![0, 1, 3]),
+                (2.5, vec![0, 2, 3]),
+                (3.0, vec![0, 1, 2, 3]),
+            ]
+        );
+    }
+
+    #[test]
+    fn matches_every_simple_path_on_random_graphs() {
+        for seed in 0..60 {
+            let graph = CsrGraph::random(seed, 8, 3);
+            let reverse = graph.transpose();
+            let (source, target) = (0, 7);
+            let Some((distance, shortest)) =
+                bidirectional_dijkstra(&graph, &reverse, source, target)
+            else {
+                assert!(all_simple_paths(&graph, source, target).is_empty());
+                continue;
+            };
+            let expected = all_simple_paths(&graph, source, target);
+            let found = k_shortest_paths(&graph, shortest.clone(), 6, |_| 0.0);
+            // an exact lower bound only changes the order detours are searched in
+            let to_target = paths::distances_from(&reverse, target);
+            let guided =
+                k_shortest_paths(&graph, shortest.clone(), 6, |node| to_target[node as usize]);
+            assert_eq!(guided.len(), found.len(), "seed {seed}");
+            for ((guided, _), (distance, _)) in guided.iter().zip(&found) {
+                assert!((guided - distance).abs() < 1e-4, "seed {seed}");
+            }
+
+            assert_eq!(found[0].1, shortest, "seed {seed}");
+            assert!((found[0].0 - distance).abs() < 1e-4, "seed {seed}");
+            assert_eq!(found.len(), expected.len().min(6), "seed {seed}");
+            for ((distance, path), (expected, _)) in found.iter().zip(&expected) {
+                assert!((distance - expected).abs() < 1e-4, "seed {seed}");
+                assert!((path_distance(&graph, path) - distance).abs() < 1e-4);
+                assert_eq!((path[0], path[path.len() - 1]), (source, target));
+                let mut nodes = path.clone();
+                nodes.sort_unstable();
+                nodes.dedup();
+                assert_eq!(nodes.len(), path.len(), "seed {seed} revisits a node");
+            }
+            assert!(found.windows(2).all(|pair| pair[0].0 <= pair[1].0));
+            let distinct: HashSet<&Vec<u32>> = found.iter().map(|(_, path)| path).collect();
+            assert_eq!(distinct.len(), found.len(), "seed {seed} repeats a path");
+        }
+    }
+
+    #[test]
+    fn no_paths_for_zero_or_a_single_node() {
+        let graph = CsrGraph::from_edges(2, &[(0, 1, 0.0)]);
+        assert!(k_shortest_paths(&graph, vec![0, 1], 0, |_| 0.0).is_empty());
+        assert_eq!(
+            k_shortest_paths(&graph, vec![0], 3, |_| 0.0),
+            vec![(0.0, vec![0])]
+        );
+    }
+}
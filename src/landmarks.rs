@@ -0,0 +1,213 @@
+use rayon::prelude::*;
+
+use crate::csr::CsrGraph;
+use crate::error::{Result, WikiSearchError};
+use crate::paths::distances_from;
+use crate::storage::{Section, valid_ids};
+
+pub const DEFAULT_LANDMARK_COUNT: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum LandmarkSelection {
+    /// The nodes with the most in and out links
+    HighestDegree,
+    /// Start from the best connected node, then keep adding the node farthest from
+    /// every landmark picked so far
+    Farthest,
+}
+
+/// Precomputed distances to and from a few landmark nodes, giving A* (ALT) lower bounds
+/// through the triangle inequality.
+#[derive(Debug)]
+pub struct Landmarks {
+    nodes: Section<u32>,
+    node_count: usize,
+    // from[i * node_count + v] = d(landmark i, v) and to[i * node_count + v] = d(v, landmark i),
+    // infinite when unreachable
+    from: Section<f32>,
+    to: Section<f32>,
+}
+
+impl Landmarks {
+    pub fn select(
+        graph: &CsrGraph,
+        reverse: &CsrGraph,
+        count: usize,
+        selection: LandmarkSelection,
+    ) -> Self {
+        let by_degree = || {
+            let mut nodes: Vec<u32> = (0..graph.node_count() as u32).collect();
+            nodes.par_sort_unstable_by_key(|&n| {
+                std::cmp::Reverse(graph.out_degree(n) + reverse.out_degree(n))
+            });
+            nodes
+        };
+        let sweep = |node: u32| {
+            rayon::join(
+                || distances_from(graph, node),
+                || distances_from(reverse, node),
+            )
+        };
+
+        let mut nodes: Vec<u32> = Vec::new();
+        let (mut from, mut to): (Vec<Vec<f32>>, Vec<Vec<f32>>) = (Vec::new(), Vec::new());
+        match selection {
+            LandmarkSelection::HighestDegree => {
+                nodes = by_degree().into_iter().take(count).collect();
+                (from, to) = nodes.par_iter().map(|&n| sweep(n)).unzip();
+            }
+            LandmarkSelection::Farthest => {
+                let mut next = by_degree().first().copied();
+                // distance from the closest landmark to each node
+                let mut closest = vec![f32::INFINITY; graph.node_count()];
+                while let Some(node) = next
+                    && nodes.len() < count
+                {
+                    let (from_node, to_node) = sweep(node);
+                    closest
+                        .par_iter_mut()
+                        .zip(&from_node)
+                        .for_each(|(c, d)| *c = c.min(*d));
+                    nodes.push(node);
+                    from.push(from_node);
+                    to.push(to_node);
+                    // unreachable nodes stay infinite, only consider ones some landmark reaches
+                    next = (0..graph.node_count() as u32)
+                        .into_par_iter()
+                        .filter(|&n| closest[n as usize].is_finite() && closest[n as usize] > 0.0)
+                        .max_by(|&a, &b| closest[a as usize].total_cmp(&closest[b as usize]));
+                }
+            }
+        }
+        Landmarks {
+            nodes: nodes.into(),
+            node_count: graph.node_count(),
+            from: from.concat().into(),
+            to: to.concat().into(),
+        }
+    }
+
+    /// Landmarks saved by `sections`, checking they fit a graph of `node_count` nodes
+    pub fn from_sections(
+        nodes: Section<u32>,
+        from: Section<f32>,
+        to: Section<f32>,
+        node_count: usize,
+    ) -> Result<Self> {
+        let distances = nodes.len() * node_count;
+        if !valid_ids(&nodes, node_count) || from.len() != distances || to.len() != distances {
+            return Err(WikiSearchError::CorruptGraph(format!(
+                "landmark distances do not fit a graph of {node_count} nodes"
+            )));
+        }
+        Ok(Landmarks {
+            nodes,
+            node_count,
+            from,
+            to,
+        })
+    }
+
+    /// The landmark nodes and their distances, for `write_sections`
+    pub fn sections(&self) -> [&[u8]; 3] {
+        [
+            self.nodes.as_bytes(),
+            self.from.as_bytes(),
+            self.to.as_bytes(),
+        ]
+    }
+
+    /// A lower bound on the distance from `node` to `target`, infinite when some landmark
+    /// proves `target` is unreachable.
+    pub fn lower_bound(&self, node: u32, target: u32) -> f32 {
+        let (node, target) = (node as usize, target as usize);
+        if node >= self.node_count || target >= self.node_count {
+            return 0.0;
+        }
+        let mut bound = 0_f32;
+        let (from, to) = (
+            self.from.chunks_exact(self.node_count),
+            self.to.chunks_exact(self.node_count),
+        );
+        for (from, to) in from.zip(to) {
+            let (from_node, from_target) = (from[node], from[target]);
+            let (to_node, to_target) = (to[node], to[target]);
+
+            // d(node, target) >= d(L, target) - d(L, node)
+            if from_node.is_finite() {
+                if from_target.is_infinite() {
+                    return f32::INFINITY;
+                }
+                bound = bound.max(from_target - from_node);
+            }
+            // d(node, target) >= d(node, L) - d(target, L)
+            if to_target.is_finite() {
+                if to_node.is_infinite() {
+                    return f32::INFINITY;
+                }
+                bound = bound.max(to_node - to_target);
+            }
+        }
+        bound
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::paths::astar;
+
+    #[test]
+    fn lower_bounds_never_overestimate() {
+        for selection in [
+            LandmarkSelection::HighestDegree,
+            LandmarkSelection::Farthest,
+        ] {
+            for seed in 0..20 {
+                let graph = CsrGraph::random(seed, 25, 3);
+                let reverse = graph.transpose();
+                let landmarks = Landmarks::select(&graph, &reverse, 4, selection);
+                for node in 0..25 {
+                    let distances = distances_from(&graph, node);
+                    for target in 0..25 {
+                        let bound = landmarks.lower_bound(node, target);
+                        let distance = distances[target as usize];
+                        assert!(
+                            bound <= distance + 1e-4,
+                            "seed {seed}: {bound} > {distance}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn astar_with_landmarks_stays_exact() {
+        for seed in 0..30 {
+            let graph = CsrGraph::random(seed, 40, 3);
+            let reverse = graph.transpose();
+            let landmarks = Landmarks::select(&graph, &reverse, 3, LandmarkSelection::Farthest);
+            let source = seed as u32 % 40;
+            let distances = distances_from(&graph, source);
+            for target in 0..40 {
+                let found = astar(
+                    &graph,
+                    source,
+                    target,
+                    |node| landmarks.lower_bound(node, target),
+                    |_| 0.0,
+                    |_, _| true,
+                );
+                match found {
+                    Some((distance, path)) => {
+                        assert!((distance - distances[target as usize]).abs() < 1e-4);
+                        assert_eq!((path[0], path[path.len() - 1]), (source, target));
+                    }
+                    None => assert!(distances[target as usize].is_infinite(), "seed {seed}"),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,1180 @@
+//! Shortest paths, neighbourhoods and rankings over the link graph of a ZIM archive.
+//!
+//! `WikiGraph::new` and `get_all` build a graph from an archive, `save_bin` and `load_bin`
+//! store it and map it back. Names are turned into nodes with `lookup` or `resolve_name`,
+//! and nodes back into paths with `resolve`.
+//!
+//! The default `cli` feature builds the `wiki-search` binary and the `server` feature with
+//! `serve`. Services using the graph as a library can turn both off with
+//! `default-features = false`.
+
+use dashmap::DashMap;
+use lasso::ThreadedRodeo;
+use ordered_float::OrderedFloat;
+use rand::rng;
+use rand::seq::IndexedRandom;
+use rayon::prelude::*;
+use scraper::Html;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::hash_map::Entry;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Instant;
+use zim_rs::archive::Archive;
+use zim_rs::entry::Entry as ZimEntry;
+
+mod checkpoint;
+mod constraints;
+mod csr;
+mod error;
+mod extract;
+mod filter;
+mod format;
+mod href;
+mod hubs;
+mod kpaths;
+mod landmarks;
+mod pagerank;
+mod paths;
+mod progress;
+mod resolver;
+mod search;
+#[cfg(feature = "server")]
+mod server;
+mod storage;
+/// Link weights, implement `LinkWeighter` to weigh links another way
+pub mod weighting;
+
+/// Nodes of the graph, the same key as long as the graph stays loaded
+pub use lasso::Spur;
+
+pub use checkpoint::{DEFAULT_CHECKPOINT_EVERY, catch_interrupt};
+pub use constraints::PathConstraints;
+pub use error::{Result, WikiSearchError};
+pub use extract::{LinkRegion, LinkText};
+pub use filter::{FilterConfig, FilterPreset, LinkFilter};
+pub use format::{FileKind, GraphHeader, LoadWarning, SourceArchive};
+pub use href::HrefStats;
+pub use hubs::{DEFAULT_HUB_STRENGTH, HubMeasure, HubPenalty};
+pub use landmarks::{DEFAULT_LANDMARK_COUNT, LandmarkSelection};
+pub use pagerank::PageRankConfig;
+pub use progress::{
+    BuildEvent, BuildObserver, BuildProgress, BuildSummary, JsonSummary, ParseFailures,
+    TerminalProgress,
+};
+pub use resolver::{Candidate, Unresolved};
+pub use search::{SearchHit, SearchPage, SearchResults, Suggestion};
+#[cfg(feature = "server")]
+pub use server::serve;
+
+use checkpoint::CheckpointLog;
+use csr::{CsrGraph, node_id, node_key};
+use href::Dropped;
+use hubs::HubMaxima;
+use landmarks::Landmarks;
+use progress::{BuildStats, ParseFailure};
+use storage::{AliasTable, LinkTextTable, Section, StringTable};
+use weighting::{LinkContext, LinkWeighter};
+
+/// Landmarks, PageRank scores and link text are stored next to the graph they were
+/// computed from
+pub fn side_path(graph_path: &str, kind: FileKind) -> String {
+    match kind {
+        FileKind::Graph => graph_path.to_string(),
+        FileKind::Landmarks => format!("{graph_path}.landmarks"),
+        FileKind::PageRank => format!("{graph_path}.pagerank"),
+        FileKind::LinkText => format!("{graph_path}.context"),
+        FileKind::Checkpoint => format!("{graph_path}.checkpoint"),
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct LinkInfo {
+    index: usize,
+    weight: f32,
+    region: LinkRegion,
+    text: Option<LinkText>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct Page {
+    links_to_weight: HashMap<Spur, LinkInfo>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PathInfo {
+    pub distance: f32,
+    pub path: Vec<Spur>,
+}
+
+/// Everything `Page::from_entry` needs besides the entry itself
+struct LinkExtraction<'a> {
+    archive: &'a Archive,
+    interner: &'a ThreadedRodeo,
+    filter: &'a LinkFilter,
+    weighter: &'a dyn LinkWeighter,
+    capture_text: bool,
+    stats: &'a HrefStats,
+}
+
+impl Page {
+    fn from_entry(e: ZimEntry, ctx: &LinkExtraction) -> std::result::Result<Self, ParseFailure> {
+        let entry_path = e.get_path();
+        let i = e.get_item(true).map_err(|()| ParseFailure::NoItem)?;
+        let blob = i.get_data().map_err(|()| ParseFailure::Unreadable)?;
+        let d = blob.data();
+        let doc = match String::from_utf8(d.to_vec()) {
+            Ok(s) => Html::parse_document(&s),
+            Err(_) => return Err(ParseFailure::NotUtf8),
+        };
+        let raw_links = extract::extract_links(&doc, ctx.filter, ctx.capture_text);
+        let sections = raw_links.last().map_or(0, |link| link.section);
+        let new_scheme = ctx.archive.has_new_namespace_scheme();
+
+        let mut seen_paths = HashSet::new();
+        let mut all_links = Vec::new();
+        for link in &raw_links {
+            let path = match href::normalize(link.href, &entry_path, new_scheme) {
+                // href patterns name articles the same way whatever the namespace scheme
+                Ok(path)
+                    if !ctx
+                        .filter
+                        .allows_href(href::article_name(&path, new_scheme)) =>
+                {
+                    Err(Dropped::Filtered)
+                }
+                // "Paris" and "Paris#History" are one link, only the first is kept
+                Ok(path) if seen_paths.contains(&path) => Ok(path),
+                Ok(path) if !ctx.archive.has_entry_bypath(&path) => Err(Dropped::Missing),
+                result => result,
+            };
+            match path {
+                Ok(path) => {
+                    ctx.stats.record_kept(path != link.href);
+                    if seen_paths.insert(path.clone()) {
+                        // Intern the string and store the Spur key
+                        all_links.push((ctx.interner.get_or_intern(&path), link));
+                    }
+                }
+                Err(reason) => ctx.stats.record_drop(reason),
+            }
+        }
+
+        let total_links = all_links.len();
+        let links_to_weight =
+            all_links
+                .iter()
+                .enumerate()
+                .fold(HashMap::new(), |mut acc, (i, &(spur, link))| {
+                    let weight = ctx.weighter.weight(&LinkContext {
+                        index: i,
+                        total: total_links,
+                        section: link.section,
+                        sections,
+                        region: link.region,
+                    });
+                    acc.entry(spur).or_insert(LinkInfo {
+                        index: i,
+                        weight,
+                        region: link.region,
+                        text: link.text.clone(),
+                    });
+                    acc
+                });
+        Ok(Page { links_to_weight })
+    }
+}
+
+// Redirects can chain, and a broken archive could even loop
+const MAX_REDIRECT_HOPS: usize = 8;
+
+/// Follows a redirect entry to the article it finally points at.
+fn resolve_redirect(e: ZimEntry) -> Option<ZimEntry> {
+    let mut current = e;
+    for _ in 0..MAX_REDIRECT_HOPS {
+        if !current.is_redirect() {
+            return Some(current);
+        }
+        current = current.get_redirect_entry().ok()?;
+    }
+    None
+}
+
+fn notify(observers: &mut [Box<dyn BuildObserver>], event: BuildEvent) {
+    for observer in observers {
+        observer.on_event(event);
+    }
+}
+
+// number of sections in a graph file, see `save_bin`
+const GRAPH_SECTIONS: usize = 13;
+
+pub struct WikiGraph {
+    a: Archive,
+    // where the graph came from and how it was built
+    header: GraphHeader,
+    // turns link positions into edge weights, named in `header`. `None` when a loaded graph
+    // names a weighting this build does not know, no pages can be added to it then
+    weighter: Option<Arc<dyn LinkWeighter>>,
+    // decides which links make it into the graph, its config is in `header`
+    filter: Arc<LinkFilter>,
+
+    // Build state, only filled while pages are being added
+    // pages parsed so far, drained into `graph` by `freeze`
+    link_to_page: DashMap<Spur, Page>,
+    // redirect path -> canonical article path, frozen into `redirects`
+    aliases: DashMap<Spur, Spur>,
+    // every path seen so far, frozen into `names`
+    interner: Arc<ThreadedRodeo>,
+    // whether pages added now keep the text around their links
+    capture_text: bool,
+    // what became of the hrefs of the pages added so far
+    href_stats: HrefStats,
+    // archive entries `get_all` has gone through, in `iter_efficient` order
+    entries_done: usize,
+    // where `get_all` saves its progress and after how many entries
+    checkpoint: Option<(String, usize)>,
+    // what the last checkpoint file already holds, later checkpoints only add to it
+    checkpointed: CheckpointLog,
+    // pages, redirects and failures counted by `get_all`, across resumed runs
+    build_stats: BuildStats,
+    // told how `get_all` is getting on
+    observers: Vec<Box<dyn BuildObserver>>,
+
+    // Frozen state that searches run against, borrowed from the mapped file once loaded
+    names: StringTable,
+    redirects: AliasTable,
+    graph: CsrGraph,
+    // `graph` with its edges reversed, for searching backwards from a target
+    reverse: CsrGraph,
+    // lower bounds for A*, searches fall back to bidirectional Dijkstra without them
+    landmarks: Option<Landmarks>,
+    // importance of every node, indexed by node id
+    pagerank: Option<Section<f32>>,
+    // what `find_shortest_path_penalized` divides hub scores by
+    hub_maxima: HubMaxima,
+    // anchor text and sentence of every edge of `graph`, kept in a side file
+    link_text: Option<LinkTextTable>,
+    // what was off about the files the graph was loaded or resumed from
+    warnings: Vec<LoadWarning>,
+}
+
+impl WikiGraph {
+    pub fn new(
+        file_path: &str,
+        weighter: Arc<dyn LinkWeighter>,
+        filter: LinkFilter,
+    ) -> Result<Self> {
+        let a = format::open_archive(file_path)?;
+        Ok(WikiGraph {
+            header: GraphHeader::new(&a, &weighter.name(), filter.config()),
+            a,
+            weighter: Some(weighter),
+            filter: Arc::new(filter),
+            link_to_page: DashMap::new(),
+            aliases: DashMap::new(),
+            interner: Arc::new(ThreadedRodeo::new()),
+            names: StringTable::default(),
+            redirects: AliasTable::default(),
+            graph: CsrGraph::default(),
+            reverse: CsrGraph::default(),
+            link_text: None,
+            capture_text: false,
+            href_stats: HrefStats::default(),
+            entries_done: 0,
+            checkpoint: None,
+            checkpointed: CheckpointLog::default(),
+            build_stats: BuildStats::default(),
+            observers: Vec::new(),
+            landmarks: None,
+            pagerank: None,
+            hub_maxima: HubMaxima::default(),
+            warnings: Vec::new(),
+        })
+    }
+
+    /// Rebuilds the build state of a loaded graph so more pages can be added to it
+    fn thaw(&mut self) -> Result<()> {
+        if self.weighter.is_none() {
+            return Err(WikiSearchError::Unsupported(format!(
+                "the graph was weighted with {:?}, which this build does not know, so no \
+                 pages can be added to it",
+                self.header.weighting
+            )));
+        }
+        if !self.interner.is_empty() || self.names.len() == 0 {
+            return Ok(());
+        }
+        // interning in order keeps every Spur the same
+        for name in self.names.iter() {
+            self.interner.get_or_intern(name);
+        }
+        for (redirect, target) in self.redirects.iter() {
+            self.aliases.insert(node_key(redirect), node_key(target));
+        }
+        Ok(())
+    }
+
+    /// Parses the article at path `link`, `false` if it was already added
+    pub fn add_link(&mut self, link: &str) -> Result<bool> {
+        self.thaw()?;
+        let link_key = self.interner.get_or_intern(link);
+        if self.link_to_page.contains_key(&link_key) || self.aliases.contains_key(&link_key) {
+            return Ok(false);
+        }
+        let e = self
+            .a
+            .get_entry_bypath_str(link)
+            .ok()
+            .and_then(resolve_redirect)
+            .ok_or_else(|| WikiSearchError::MissingEntry {
+                path: link.to_string(),
+            })?;
+        let path = e.get_path();
+        let canonical_key = self.interner.get_or_intern(&path);
+        if canonical_key != link_key {
+            self.aliases.insert(link_key, canonical_key);
+            if self.link_to_page.contains_key(&canonical_key) {
+                return Ok(false);
+            }
+        }
+        let page =
+            Page::from_entry(e, &self.extraction()?).map_err(|reason| reason.error(&path))?;
+        self.link_to_page.insert(canonical_key, page);
+        Ok(true)
+    }
+
+    /// Records `e` as an alias of the article it redirects to
+    fn add_redirect(&self, e: ZimEntry) {
+        let path_key = self.interner.get_or_intern(e.get_path());
+        if let Some(target) = resolve_redirect(e) {
+            let target_key = self.interner.get_or_intern(target.get_path());
+            if target_key != path_key {
+                self.aliases.insert(path_key, target_key);
+            }
+        }
+    }
+
+    /// Rewrites every edge that lands on a redirect so it points at the canonical article.
+    fn collapse_redirects(&self) {
+        self.link_to_page.par_iter_mut().for_each(|mut page| {
+            if !page
+                .links_to_weight
+                .keys()
+                .any(|link| self.aliases.contains_key(link))
+            {
+                return;
+            }
+            let links = std::mem::take(&mut page.links_to_weight);
+            for (link, info) in links {
+                let link = self.canonical(link);
+                // several redirects can collapse onto one article, keep the earliest link
+                match page.links_to_weight.entry(link) {
+                    Entry::Occupied(mut o) => {
+                        if info.index < o.get().index {
+                            o.insert(info);
+                        }
+                    }
+                    Entry::Vacant(v) => {
+                        v.insert(info);
+                    }
+                }
+            }
+        });
+    }
+
+    /// Moves the parsed pages into the read-optimised graph that searches run against.
+    ///
+    /// `get_all` does this itself, call it after `add_link` to make the new pages searchable.
+    pub fn freeze(&mut self) {
+        self.collapse_redirects();
+        let pages = std::mem::take(&mut self.link_to_page);
+        let old_text = self.link_text.take();
+        // pages frozen earlier are only in the graph, carry them over
+        for node in 0..self.graph.node_count() as u32 {
+            let key = node_key(node);
+            if pages.contains_key(&key) || self.graph.out_degree(node) == 0 {
+                continue;
+            }
+            let links_to_weight = self
+                .graph
+                .outlinks(node)
+                .zip(self.graph.outlink_regions(node))
+                .zip(self.graph.row(node))
+                .enumerate()
+                .map(|(index, (((link, weight), region), edge))| {
+                    let info = LinkInfo {
+                        index,
+                        weight,
+                        region,
+                        text: old_text.as_ref().and_then(|texts| texts.get(edge)),
+                    };
+                    (node_key(link), info)
+                })
+                .collect();
+            pages.insert(key, Page { links_to_weight });
+        }
+        self.graph = CsrGraph::from_pages(&pages, self.interner.len());
+        if self.capture_text {
+            self.link_text = Some(self.collect_link_text(&pages));
+        }
+        self.reverse = self.graph.transpose();
+        self.names = StringTable::from_interner(&self.interner);
+        self.redirects = AliasTable::from_pairs(
+            self.aliases
+                .iter()
+                .map(|alias| (node_id(*alias.key()), node_id(*alias.value())))
+                .collect(),
+        );
+        self.header = GraphHeader::new(&self.a, &self.header.weighting, self.filter.config());
+        // distances changed, the old landmarks could now overestimate
+        self.landmarks = None;
+        self.pagerank = None;
+        self.hub_maxima = HubMaxima::default();
+    }
+
+    /// Anchor texts of `pages` laid out like the edges of the freshly built `graph`
+    fn collect_link_text(&self, pages: &DashMap<Spur, Page>) -> LinkTextTable {
+        let mut texts = vec![LinkText::default(); self.graph.edge_count()];
+        for page in pages.iter() {
+            let row = self.graph.row(node_id(*page.key()));
+            // rows are in document order, see `CsrGraph::from_pages`
+            let mut links: Vec<&LinkInfo> = page.links_to_weight.values().collect();
+            links.sort_unstable_by_key(|info| info.index);
+            for (edge, info) in row.zip(links) {
+                texts[edge] = info.text.clone().unwrap_or_default();
+            }
+        }
+        LinkTextTable::from_texts(&texts)
+    }
+
+    /// What parsing a page needs, an error when new links cannot be weighted, see `thaw`
+    fn extraction(&self) -> Result<LinkExtraction<'_>> {
+        let weighter = self.weighter.as_deref().ok_or_else(|| {
+            WikiSearchError::Unsupported("this graph cannot weigh new links".to_string())
+        })?;
+        Ok(LinkExtraction {
+            archive: &self.a,
+            interner: &self.interner,
+            filter: &self.filter,
+            weighter,
+            capture_text: self.capture_text,
+            stats: &self.href_stats,
+        })
+    }
+
+    /// Reports the progress of `get_all` to `observer` as well as any added before
+    pub fn observe_build(&mut self, observer: Box<dyn BuildObserver>) {
+        self.observers.push(observer);
+    }
+
+    /// What became of the hrefs of every page added since the graph was created or loaded
+    pub fn href_stats(&self) -> &HrefStats {
+        &self.href_stats
+    }
+
+    /// Keep the anchor text and sentence of every link added from now on, saved next to
+    /// the graph so paths can be explained
+    pub fn capture_link_text(&mut self, capture: bool) {
+        self.capture_text = capture;
+    }
+
+    /// Maps a redirect to the article it points at, any other key is returned unchanged
+    pub fn canonical(&self, key: Spur) -> Spur {
+        if let Some(target) = self.aliases.get(&key) {
+            return *target;
+        }
+        self.redirects.get(node_id(key)).map_or(key, node_key)
+    }
+
+    /// The canonical node for `path`, `None` if no page or link has that path
+    pub fn lookup(&self, path: &str) -> Option<Spur> {
+        let key = self
+            .names
+            .get(path)
+            .map(node_key)
+            .or_else(|| self.interner.get(path))?;
+        Some(self.canonical(key))
+    }
+
+    /// Adds every article of the archive and freezes the graph.
+    ///
+    /// Returns `false` without freezing when interrupted, after saving a checkpoint if
+    /// `checkpoint_to` was called.
+    pub fn get_all(&mut self) -> Result<bool> {
+        self.thaw()?;
+        let start = Instant::now();
+        let total_entries = self.a.get_entrycount() as usize;
+        let skipped = self.entries_done;
+        let mut entry_iter = self
+            .a
+            .iter_efficient()
+            .map_err(|()| WikiSearchError::Archive("cannot iterate over the entries".to_string()))?
+            .into_iter()
+            .skip(skipped);
+        notify(
+            &mut self.observers,
+            BuildEvent::Started {
+                total_entries,
+                skipped,
+            },
+        );
+        let progress = |wiki_graph: &Self| {
+            BuildProgress::of(
+                &wiki_graph.build_stats,
+                wiki_graph.entries_done,
+                total_entries,
+                skipped,
+                start,
+            )
+        };
+        let mut since_checkpoint = 0;
+        loop {
+            if checkpoint::interrupted() {
+                if let Some((path, _)) = self.checkpoint.clone() {
+                    self.save_checkpoint(&path)?;
+                    notify(&mut self.observers, BuildEvent::Checkpoint { path: &path });
+                }
+                let progress = progress(self);
+                notify(&mut self.observers, BuildEvent::Interrupted(&progress));
+                return Ok(false);
+            }
+            if let Some((path, every)) = self.checkpoint.clone()
+                && since_checkpoint >= every
+            {
+                self.save_checkpoint(&path)?;
+                notify(&mut self.observers, BuildEvent::Checkpoint { path: &path });
+                since_checkpoint = 0;
+            }
+            let entries: Vec<_> = entry_iter.by_ref().take(100).collect();
+            if entries.is_empty() {
+                break;
+            }
+            self.entries_done += entries.len();
+            since_checkpoint += entries.len();
+
+            let extraction = self.extraction()?;
+            entries.into_iter().par_bridge().for_each(|e| {
+                let Ok(e) = e else {
+                    self.build_stats.record_failure(ParseFailure::Unreadable);
+                    return;
+                };
+                if e.is_redirect() {
+                    self.build_stats.record_redirect();
+                    self.add_redirect(e);
+                    return;
+                }
+                let path = e.get_path();
+                match Page::from_entry(e, &extraction) {
+                    Ok(p) => {
+                        self.build_stats.record_page(p.links_to_weight.len());
+                        let path_key = self.interner.get_or_intern(&path);
+                        self.link_to_page.insert(path_key, p);
+                    }
+                    Err(reason) => self.build_stats.record_failure(reason),
+                }
+            });
+            let progress = progress(self);
+            notify(&mut self.observers, BuildEvent::Progress(&progress));
+        }
+        let progress = progress(self);
+        self.freeze();
+        let summary = BuildSummary {
+            header: &self.header,
+            progress,
+            hrefs: &self.href_stats,
+            nodes: self.names.len(),
+            edges: self.graph.edge_count(),
+            aliases: self.redirects.len(),
+        };
+        notify(&mut self.observers, BuildEvent::Finished(&summary));
+        Ok(true)
+    }
+
+    pub fn save_bin(&self, graph_path: &str) -> Result<()> {
+        let sections: Vec<&[u8]> = [
+            &self.graph.sections()[..],
+            &self.reverse.sections(),
+            &self.names.sections(),
+            &self.redirects.sections(),
+        ]
+        .concat();
+        debug_assert_eq!(sections.len(), GRAPH_SECTIONS);
+        format::write_sections(graph_path, FileKind::Graph, &self.header, &sections)?;
+        if let Some(texts) = &self.link_text {
+            let path = side_path(graph_path, FileKind::LinkText);
+            format::write_sections(&path, FileKind::LinkText, &self.header, &texts.sections())?;
+        }
+        Ok(())
+    }
+
+    /// Maps the saved graph into memory, searches then read it in place without a copy.
+    pub fn load_bin(zim_path: &str, graph_path: &str) -> Result<Self> {
+        let a = format::open_archive(zim_path)?;
+
+        let (header, map, sections) = format::map_sections(graph_path, FileKind::Graph)?;
+        let mut warnings = Vec::new();
+        header.check_source(&a, &mut warnings)?;
+        if sections.len() != GRAPH_SECTIONS {
+            return Err(WikiSearchError::corrupt_file(
+                graph_path,
+                format!("expected {GRAPH_SECTIONS} sections"),
+            ));
+        }
+        let mut sections = sections.into_iter();
+        let mut next = || sections.next().unwrap();
+
+        let graph = CsrGraph::from_sections(
+            Section::mapped(&map, next())?,
+            Section::mapped(&map, next())?,
+            Section::mapped(&map, next())?,
+            Section::mapped(&map, next())?,
+        )?;
+        let reverse = CsrGraph::from_sections(
+            Section::mapped(&map, next())?,
+            Section::mapped(&map, next())?,
+            Section::mapped(&map, next())?,
+            Section::mapped(&map, next())?,
+        )?;
+        let names = StringTable::from_sections(
+            Section::mapped(&map, next())?,
+            Section::mapped(&map, next())?,
+            Section::mapped(&map, next())?,
+        )?;
+        if reverse.node_count() != graph.node_count() || names.len() != graph.node_count() {
+            return Err(WikiSearchError::CorruptGraph(format!(
+                "{} nodes in the graph, {} in its reverse and {} names",
+                graph.node_count(),
+                reverse.node_count(),
+                names.len()
+            )));
+        }
+        let redirects = AliasTable::from_sections(
+            Section::mapped(&map, next())?,
+            Section::mapped(&map, next())?,
+        )?;
+
+        // Landmarks and PageRank scores are optional, they are computed separately
+        let landmarks = match format::map_side_file(
+            &side_path(graph_path, FileKind::Landmarks),
+            FileKind::Landmarks,
+            &header,
+            &mut warnings,
+        )? {
+            Some((map, sections)) => {
+                let [nodes, from, to] = <[_; 3]>::try_from(sections).map_err(|_| {
+                    WikiSearchError::CorruptGraph("landmarks expect 3 sections".to_string())
+                })?;
+                Some(Landmarks::from_sections(
+                    Section::mapped(&map, nodes)?,
+                    Section::mapped(&map, from)?,
+                    Section::mapped(&map, to)?,
+                    graph.node_count(),
+                )?)
+            }
+            None => None,
+        };
+        let pagerank = match format::map_side_file(
+            &side_path(graph_path, FileKind::PageRank),
+            FileKind::PageRank,
+            &header,
+            &mut warnings,
+        )? {
+            Some((map, sections)) => {
+                let [scores] = <[_; 1]>::try_from(sections).map_err(|_| {
+                    WikiSearchError::CorruptGraph("PageRank expects 1 section".to_string())
+                })?;
+                let scores: Section<f32> = Section::mapped(&map, scores)?;
+                if scores.len() != graph.node_count() {
+                    return Err(WikiSearchError::CorruptGraph(format!(
+                        "{} PageRank scores for {} nodes",
+                        scores.len(),
+                        graph.node_count()
+                    )));
+                }
+                Some(scores)
+            }
+            None => None,
+        };
+        let link_text = match format::map_side_file(
+            &side_path(graph_path, FileKind::LinkText),
+            FileKind::LinkText,
+            &header,
+            &mut warnings,
+        )? {
+            Some((map, sections)) => {
+                let [anchor_offsets, anchors, context_offsets, contexts] =
+                    <[_; 4]>::try_from(sections).map_err(|_| {
+                        WikiSearchError::CorruptGraph("link text expects 4 sections".to_string())
+                    })?;
+                Some(LinkTextTable::from_sections(
+                    Section::mapped(&map, anchor_offsets)?,
+                    Section::mapped(&map, anchors)?,
+                    Section::mapped(&map, context_offsets)?,
+                    Section::mapped(&map, contexts)?,
+                    graph.edge_count(),
+                )?)
+            }
+            None => None,
+        };
+
+        // pages added to a loaded graph must be weighted like the ones already in it, there
+        // is no adding any if its weighting is unknown here
+        let weighter = weighting::from_name(&header.weighting);
+        let filter = LinkFilter::new(header.link_filter.clone())?;
+
+        Ok(WikiGraph {
+            a,
+            header,
+            weighter,
+            filter: Arc::new(filter),
+            link_to_page: DashMap::new(),
+            aliases: DashMap::new(),
+            interner: Arc::new(ThreadedRodeo::new()),
+            names,
+            redirects,
+            graph,
+            reverse,
+            capture_text: link_text.is_some(),
+            href_stats: HrefStats::default(),
+            entries_done: 0,
+            checkpoint: None,
+            checkpointed: CheckpointLog::default(),
+            build_stats: BuildStats::default(),
+            observers: Vec::new(),
+            link_text,
+            landmarks,
+            pagerank,
+            hub_maxima: HubMaxima::default(),
+            warnings,
+        })
+    }
+
+    /// Precomputes distances to and from `count` landmarks so `find_shortest_path` can use A*
+    pub fn compute_landmarks(&mut self, count: usize, selection: LandmarkSelection) {
+        self.landmarks = Some(Landmarks::select(
+            &self.graph,
+            &self.reverse,
+            count,
+            selection,
+        ));
+    }
+
+    pub fn save_landmarks(&self, graph_path: &str) -> Result<()> {
+        if let Some(landmarks) = &self.landmarks {
+            let path = side_path(graph_path, FileKind::Landmarks);
+            format::write_sections(
+                &path,
+                FileKind::Landmarks,
+                &self.header,
+                &landmarks.sections(),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// The archive the graph was built from
+    pub fn archive(&self) -> &Archive {
+        &self.a
+    }
+
+    pub fn header(&self) -> &GraphHeader {
+        &self.header
+    }
+
+    /// What was off about the files the graph was loaded or resumed from, none of it
+    /// stopped it from loading
+    pub fn warnings(&self) -> &[LoadWarning] {
+        &self.warnings
+    }
+
+    /// Every interned path, including link targets without a page
+    pub fn node_count(&self) -> usize {
+        self.graph.node_count()
+    }
+
+    /// Nodes with at least one outlink
+    pub fn page_count(&self) -> usize {
+        (0..self.graph.node_count() as u32)
+            .into_par_iter()
+            .filter(|&node| self.graph.out_degree(node) > 0)
+            .count()
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.graph.edge_count()
+    }
+
+    pub fn redirect_count(&self) -> usize {
+        self.redirects.len()
+    }
+
+    pub fn has_landmarks(&self) -> bool {
+        self.landmarks.is_some()
+    }
+
+    pub fn has_pagerank(&self) -> bool {
+        self.pagerank.is_some()
+    }
+
+    pub fn out_degree(&self, page: Spur) -> usize {
+        self.graph.out_degree(node_id(self.canonical(page)))
+    }
+
+    /// Weight of the link from `from` to `to`, if `from` links to it
+    pub fn link_weight(&self, from: Spur, to: Spur) -> Option<f32> {
+        self.find_link(from, to).map(|(weight, _)| weight)
+    }
+
+    /// Anchor text and sentence of the link from `from` to `to`, if they were captured
+    pub fn link_text(&self, from: Spur, to: Spur) -> Option<LinkText> {
+        let (from, to) = (node_id(self.canonical(from)), node_id(self.canonical(to)));
+        let edge = self.graph.find_edge(from, to)?;
+        self.link_text.as_ref()?.get(edge)
+    }
+
+    /// Where in `from` its link to `to` was found
+    pub fn link_region(&self, from: Spur, to: Spur) -> Option<LinkRegion> {
+        self.find_link(from, to).map(|(_, region)| region)
+    }
+
+    fn find_link(&self, from: Spur, to: Spur) -> Option<(f32, LinkRegion)> {
+        let (from, to) = (node_id(self.canonical(from)), node_id(self.canonical(to)));
+        self.graph
+            .outlinks(from)
+            .zip(self.graph.outlink_regions(from))
+            .find(|&((link, _), _)| link == to)
+            .map(|((_, weight), region)| (weight, region))
+    }
+
+    /// Distance from the start of `path` to each of its pages, 0 for the first
+    pub fn hop_distances(&self, path: &[Spur]) -> Vec<f32> {
+        let mut distance = 0.0;
+        let mut distances = Vec::with_capacity(path.len());
+        distances.push(distance);
+        for hop in path.windows(2) {
+            distance += self.link_weight(hop[0], hop[1]).unwrap_or(f32::INFINITY) + 1_f32;
+            distances.push(distance);
+        }
+        distances.truncate(path.len());
+        distances
+    }
+
+    /// `path` with the distance from its first page to its last
+    pub fn path_info(&self, path: Vec<Spur>) -> PathInfo {
+        PathInfo {
+            distance: self.hop_distances(&path).last().copied().unwrap_or(0.0),
+            path,
+        }
+    }
+
+    /// Title of the article at a node, its path when the archive has no such entry
+    pub fn title(&self, key: Spur) -> String {
+        let path = self.resolve(key);
+        self.a
+            .get_entry_bypath_str(path)
+            .map_or_else(|()| path.to_string(), |e| e.get_title())
+    }
+
+    /// Path of a node
+    pub fn resolve(&self, key: Spur) -> &str {
+        self.names
+            .try_resolve(node_id(key))
+            .or_else(|| self.interner.try_resolve(&key))
+            .unwrap_or_default()
+    }
+
+    pub fn compute_pagerank(&mut self, config: &PageRankConfig) {
+        self.pagerank = Some(pagerank::pagerank(&self.graph, &self.reverse, config).into());
+        self.hub_maxima = HubMaxima::default();
+    }
+
+    pub fn save_pagerank(&self, graph_path: &str) -> Result<()> {
+        if let Some(scores) = &self.pagerank {
+            let path = side_path(graph_path, FileKind::PageRank);
+            format::write_sections(
+                &path,
+                FileKind::PageRank,
+                &self.header,
+                &[scores.as_bytes()],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// PageRank of `page`, `None` until scores are computed or loaded
+    pub fn pagerank(&self, page: Spur) -> Option<f32> {
+        let scores = self.pagerank.as_ref()?;
+        Some(
+            scores
+                .get(node_id(self.canonical(page)) as usize)
+                .copied()
+                .unwrap_or(0.0),
+        )
+    }
+
+    /// The `count` pages with the highest PageRank, best first
+    pub fn top_pages(&self, count: usize) -> Vec<(Spur, f32)> {
+        let Some(scores) = &self.pagerank else {
+            return Vec::new();
+        };
+        let mut ranked: Vec<(u32, f32)> = (0..scores.len() as u32)
+            .zip(scores.iter().copied())
+            .filter(|&(node, _)| self.graph.out_degree(node) > 0)
+            .collect();
+        ranked.par_sort_unstable_by(|a, b| b.1.total_cmp(&a.1));
+        ranked
+            .into_iter()
+            .take(count)
+            .map(|(node, score)| (node_key(node), score))
+            .collect()
+    }
+
+    /// Pages most related to `seed` by a random walk that keeps restarting from it
+    pub fn related_articles(
+        &self,
+        seed: Spur,
+        count: usize,
+        config: &PageRankConfig,
+    ) -> Vec<(Spur, f32)> {
+        let seed = node_id(self.canonical(seed));
+        let scores = pagerank::personalized_pagerank(&self.graph, &self.reverse, config, &[seed]);
+        let mut ranked: Vec<(u32, f32)> = (0..scores.len() as u32)
+            .zip(scores)
+            .filter(|&(node, score)| node != seed && score > 0.0 && self.graph.out_degree(node) > 0)
+            .collect();
+        ranked.par_sort_unstable_by(|a, b| b.1.total_cmp(&a.1));
+        ranked
+            .into_iter()
+            .take(count)
+            .map(|(node, score)| (node_key(node), score))
+            .collect()
+    }
+
+    /// Pages `page` links to with the weight of each link
+    pub fn outlinks(&self, page: Spur) -> impl Iterator<Item = (Spur, f32)> + '_ {
+        self.graph
+            .outlinks(node_id(self.canonical(page)))
+            .map(|(link, weight)| (node_key(link), weight))
+    }
+
+    /// Pages linking to `page` with the weight of their link to it
+    pub fn inlinks(&self, page: Spur) -> impl Iterator<Item = (Spur, f32)> + '_ {
+        self.reverse
+            .outlinks(node_id(self.canonical(page)))
+            .map(|(link, weight)| (node_key(link), weight))
+    }
+
+    pub fn in_degree(&self, page: Spur) -> usize {
+        self.reverse.out_degree(node_id(self.canonical(page)))
+    }
+
+    pub fn get_random_article(&self) -> Option<Spur> {
+        let entry = self.a.get_randomentry().ok()?;
+        self.lookup(&entry.get_path())
+    }
+
+    /// A random article that has outlinks in the graph, giving up after `attempts` draws
+    pub fn random_page(&self, attempts: usize) -> Option<Spur> {
+        (0..attempts)
+            .filter_map(|_| self.get_random_article())
+            .find(|&page| self.out_degree(page) > 0)
+    }
+
+    pub fn get_close_titles(
+        &self,
+        first_link: Spur,
+        count: usize,
+        min_distance: f32,
+        max_distance: f32,
+    ) -> Vec<PathInfo> {
+        let candidates: Vec<_> = self
+            .iter_close_titles(first_link, min_distance, Some(max_distance))
+            .filter(|info| info.path.len() > 1) // the start page itself
+            .collect();
+
+        let mut rng = rng();
+        candidates
+            .choose_multiple(&mut rng, count)
+            .cloned()
+            .collect()
+    }
+
+    pub fn find_shortest_path(&self, first_link: Spur, target_link: Spur) -> Option<Vec<Spur>> {
+        let (source, target) = (
+            node_id(self.canonical(first_link)),
+            node_id(self.canonical(target_link)),
+        );
+        let (_, path) = match &self.landmarks {
+            Some(landmarks) => paths::astar(
+                &self.graph,
+                source,
+                target,
+                |node| landmarks.lower_bound(node, target),
+                |_| 0.0,
+                |_, _| true,
+            )?,
+            None => paths::bidirectional_dijkstra(&self.graph, &self.reverse, source, target)?,
+        };
+        Some(path.into_iter().map(node_key).collect())
+    }
+
+    /// Up to `k` paths from `first_link` to `target_link` that never visit a page twice,
+    /// shortest first, found with Yen's algorithm. The first is `find_shortest_path`.
+    pub fn find_k_shortest_paths(
+        &self,
+        first_link: Spur,
+        target_link: Spur,
+        k: usize,
+    ) -> Vec<PathInfo> {
+        let Some(shortest) = self.find_shortest_path(first_link, target_link) else {
+            return Vec::new();
+        };
+        let shortest: Vec<u32> = shortest.into_iter().map(node_id).collect();
+        let target = shortest[shortest.len() - 1];
+        kpaths::k_shortest_paths(&self.graph, shortest, k, |node| match &self.landmarks {
+            Some(landmarks) => landmarks.lower_bound(node, target),
+            None => 0.0,
+        })
+        .into_iter()
+        .map(|(distance, path)| PathInfo {
+            distance,
+            path: path.into_iter().map(node_key).collect(),
+        })
+        .collect()
+    }
+
+    pub fn iter_close_titles(
+        &self,
+        first_link: Spur,
+        min_distance: f32,
+        max_distance: Option<f32>,
+    ) -> ClosestPagesIter<'_> {
+        let first_node = node_id(self.canonical(first_link));
+        let mut next_pages = BinaryHeap::new();
+        next_pages.push(PrioritizedPage {
+            priority: Reverse(OrderedFloat(0.0)),
+            node: first_node,
+        });
+
+        ClosestPagesIter {
+            wiki_graph: self,
+            visited: HashSet::new(),
+            parents: HashMap::from([(first_node, (first_node, 0.0))]),
+            next_pages,
+            min_distance,
+            max_distance,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct PrioritizedPage {
+    pub priority: Reverse<OrderedFloat<f32>>,
+    pub node: u32,
+}
+
+impl PartialEq for PrioritizedPage {
+    fn eq(&self, other: &Self) -> bool {
+        self.priority.eq(&other.priority)
+    }
+}
+
+impl Eq for PrioritizedPage {}
+
+impl PartialOrd for PrioritizedPage {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        self.priority.partial_cmp(&other.priority)
+    }
+}
+
+impl Ord for PrioritizedPage {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.priority.cmp(&other.priority)
+    }
+}
+
+pub struct ClosestPagesIter<'a> {
+    wiki_graph: &'a WikiGraph,
+    visited: HashSet<u32>,
+    // node -> (node it was best reached from, distance), the start is its own parent
+    parents: HashMap<u32, (u32, f32)>,
+    next_pages: BinaryHeap<PrioritizedPage>,
+    min_distance: f32,
+    max_distance: Option<f32>,
+}
+
+impl ClosestPagesIter<'_> {
+    fn path_to(&self, node: u32) -> Vec<Spur> {
+        let mut path = vec![node_key(node)];
+        let mut current = node;
+        while let Some(&(parent, _)) = self.parents.get(&current)
+            && parent != current
+        {
+            path.push(node_key(parent));
+            current = parent;
+        }
+        path.reverse();
+        path
+    }
+}
+
+impl<'a> Iterator for ClosestPagesIter<'a> {
+    type Item = PathInfo;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(p) = self.next_pages.pop() {
+            // Skip if already visited
+            if !self.visited.insert(p.node) {
+                continue;
+            }
+
+            let distance = p.priority.0.0;
+
+            // Stop if we've exceeded max distance
+            if self
+                .max_distance
+                .is_some_and(|max_distance| distance > max_distance)
+            {
+                return None;
+            }
+
+            // Add neighbors to priority queue
+            for (link, weight) in self.wiki_graph.graph.outlinks(p.node) {
+                let total_distance = distance + weight + 1_f32;
+
+                if self
+                    .max_distance
+                    .is_some_and(|max_distance| total_distance > max_distance)
+                    || self.visited.contains(&link)
+                    || self
+                        .parents
+                        .get(&link)
+                        .is_some_and(|&(_, best)| best <= total_distance)
+                {
+                    continue;
+                }
+
+                self.parents.insert(link, (p.node, total_distance));
+                self.next_pages.push(PrioritizedPage {
+                    priority: Reverse(OrderedFloat(total_distance)),
+                    node: link,
+                });
+            }
+
+            // Return this page if it's within the distance range
+            if distance >= self.min_distance
+                && self
+                    .max_distance
+                    .is_none_or(|max_distance| max_distance >= distance)
+            {
+                return Some(PathInfo {
+                    distance,
+                    path: self.path_to(p.node),
+                });
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,467 @@
+use ordered_float::OrderedFloat;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use crate::PrioritizedPage;
+use crate::csr::CsrGraph;
+
+/// Follows `parents` from `node` back to the node that is its own parent
+pub fn trace(parents: &HashMap<u32, (u32, f32)>, node: u32) -> Vec<u32> {
+    let mut path = vec![node];
+    let mut current = node;
+    while let Some(&(parent, _)) = parents.get(&current)
+        && parent != current
+    {
+        path.push(parent);
+        current = parent;
+    }
+    path
+}
+
+/// One half of a bidirectional search, a plain Dijkstra from `start`.
+struct Frontier {
+    next_pages: BinaryHeap<PrioritizedPage>,
+    // node -> (node it was best reached from, distance), the start is its own parent
+    parents: HashMap<u32, (u32, f32)>,
+    settled: HashSet<u32>,
+}
+
+impl Frontier {
+    fn new(start: u32) -> Self {
+        Frontier {
+            next_pages: BinaryHeap::from([PrioritizedPage {
+                priority: Reverse(OrderedFloat(0.0)),
+                node: start,
+            }]),
+            parents: HashMap::from([(start, (start, 0.0))]),
+            settled: HashSet::new(),
+        }
+    }
+
+    /// Distance of the closest unsettled node, infinite once the frontier is exhausted
+    fn min_distance(&mut self) -> f32 {
+        while let Some(p) = self.next_pages.peek() {
+            if !self.settled.contains(&p.node) {
+                return p.priority.0.0;
+            }
+            self.next_pages.pop();
+        }
+        f32::INFINITY
+    }
+
+    fn distance(&self, node: u32) -> Option<f32> {
+        self.parents.get(&node).map(|&(_, distance)| distance)
+    }
+
+    /// Settles the closest node and relaxes its edges in `graph`, tracking the best
+    /// `(distance, meeting node)` seen against the opposite frontier.
+    fn settle_next(&mut self, graph: &CsrGraph, other: &Frontier, best: &mut (f32, Option<u32>)) {
+        let Some(p) = self.next_pages.pop() else {
+            return;
+        };
+        if !self.settled.insert(p.node) {
+            return;
+        }
+        let distance = p.priority.0.0;
+
+        for (link, weight) in graph.outlinks(p.node) {
+            let total_distance = distance + weight + 1_f32;
+            if self.settled.contains(&link)
+                || self
+                    .distance(link)
+                    .is_some_and(|known| known <= total_distance)
+            {
+                continue;
+            }
+
+            self.parents.insert(link, (p.node, total_distance));
+            self.next_pages.push(PrioritizedPage {
+                priority: Reverse(OrderedFloat(total_distance)),
+                node: link,
+            });
+
+            if let Some(remaining) = other.distance(link)
+                && total_distance + remaining < best.0
+            {
+                *best = (total_distance + remaining, Some(link));
+            }
+        }
+    }
+
+    /// Nodes from `node` back to the start of this frontier
+    fn trace(&self, node: u32) -> Vec<u32> {
+        trace(&self.parents, node)
+    }
+}
+
+/// Shortest path from `source` to `target` under the `1 + weight` edge cost.
+///
+/// Runs Dijkstra forward over `graph` and backward over `reverse` (its transpose),
+/// always growing the smaller frontier, and stops once no unsettled pair of nodes
+/// can beat the best meeting point found so far.
+pub fn bidirectional_dijkstra(
+    graph: &CsrGraph,
+    reverse: &CsrGraph,
+    source: u32,
+    target: u32,
+) -> Option<(f32, Vec<u32>)> {
+    if source == target {
+        return Some((0.0, vec![source]));
+    }
+
+    let mut forward = Frontier::new(source);
+    let mut backward = Frontier::new(target);
+    let mut best: (f32, Option<u32>) = (f32::INFINITY, None);
+
+    loop {
+        let forward_min = forward.min_distance();
+        let backward_min = backward.min_distance();
+        if forward_min + backward_min >= best.0 {
+            break;
+        }
+        if forward_min <= backward_min {
+            forward.settle_next(graph, &backward, &mut best);
+        } else {
+            backward.settle_next(reverse, &forward, &mut best);
+        }
+    }
+
+    let meeting = best.1?;
+    let mut path = forward.trace(meeting);
+    path.reverse();
+    path.extend(backward.trace(meeting).into_iter().skip(1));
+    Some((best.0, path))
+}
+
+/// Distance from `source` to every node of `graph`, infinite where unreachable.
+pub fn distances_from(graph: &CsrGraph, source: u32) -> Vec<f32> {
+    let mut distances = vec![f32::INFINITY; graph.node_count()];
+    let mut settled = vec![false; graph.node_count()];
+    let mut next_pages = BinaryHeap::new();
+    if let Some(d) = distances.get_mut(source as usize) {
+        *d = 0.0;
+        next_pages.push(PrioritizedPage {
+            priority: Reverse(OrderedFloat(0.0)),
+            node: source,
+        });
+    }
+
+    while let Some(p) = next_pages.pop() {
+        if std::mem::replace(&mut settled[p.node as usize], true) {
+            continue;
+        }
+        let distance = p.priority.0.0;
+        for (link, weight) in graph.outlinks(p.node) {
+            let total_distance = distance + weight + 1_f32;
+            if total_distance < distances[link as usize] {
+                distances[link as usize] = total_distance;
+                next_pages.push(PrioritizedPage {
+                    priority: Reverse(OrderedFloat(total_distance)),
+                    node: link,
+                });
+            }
+        }
+    }
+    distances
+}
+
+/// A* from `source` to `target`, `lower_bound(node)` must never overestimate the
+/// remaining distance for the result to stay optimal.
+///
+/// Stepping onto a node costs `penalty(node)` on top of the link to it, which must not be
+/// negative, and only links `follow(from, to)` accepts are taken. A lower bound for the
+/// full graph without penalties still holds with them.
+pub fn astar(
+    graph: &CsrGraph,
+    source: u32,
+    target: u32,
+    lower_bound: impl Fn(u32) -> f32,
+    penalty: impl Fn(u32) -> f32,
+    follow: impl Fn(u32, u32) -> bool,
+) -> Option<(f32, Vec<u32>)> {
+    let mut parents: HashMap<u32, (u32, f32)> = HashMap::from([(source, (source, 0.0))]);
+    let mut settled = HashSet::new();
+    let mut next_pages = BinaryHeap::from([PrioritizedPage {
+        priority: Reverse(OrderedFloat(lower_bound(source))),
+        node: source,
+    }]);
+
+    while let Some(p) = next_pages.pop() {
+        if !settled.insert(p.node) {
+            continue;
+        }
+        let distance = parents[&p.node].1;
+        if p.node == target {
+            let mut path = trace(&parents, target);
+            path.reverse();
+            return Some((distance, path));
+        }
+
+        for (link, weight) in graph.outlinks(p.node) {
+            let total_distance = distance + weight + 1_f32 + penalty(link);
+            if settled.contains(&link)
+                || !follow(p.node, link)
+                || parents
+                    .get(&link)
+                    .is_some_and(|&(_, known)| known <= total_distance)
+            {
+                continue;
+            }
+            let estimate = lower_bound(link);
+            // the target cannot be reached from here at all
+            if estimate.is_infinite() {
+                continue;
+            }
+            parents.insert(link, (p.node, total_distance));
+            next_pages.push(PrioritizedPage {
+                priority: Reverse(OrderedFloat(total_distance + estimate)),
+                node: link,
+            });
+        }
+    }
+    None
+}
+
+/// A way of reaching a node in `constrained_dijkstra`
+struct Label {
+    node: u32,
+    // waypoints visited so far
+    stage: usize,
+    // links followed, only counted when there is a limit so labels otherwise compare by
+    // distance alone
+    hops: usize,
+    distance: f32,
+    parent: Option<usize>,
+}
+
+/// Shortest walk from `source` to `target` that goes through every node of `via` in order,
+/// only steps on nodes `allowed` accepts and follows at most `max_hops` links.
+///
+/// Dijkstra over (node, waypoints reached, links followed). A label is dropped when the same
+/// node and stage were settled earlier, so at no greater distance, with no more hops.
+///
+/// Nodes are only told apart within a stage, so the walk can pass a node again after
+/// reaching the next waypoint. Without `via` there is a single stage and the walk is a path.
+pub fn constrained_dijkstra(
+    graph: &CsrGraph,
+    source: u32,
+    target: u32,
+    via: &[u32],
+    max_hops: Option<usize>,
+    mut allowed: impl FnMut(u32) -> bool,
+) -> Option<(f32, Vec<u32>)> {
+    let advance = |stage: usize, node: u32| match via.get(stage) {
+        Some(&waypoint) if waypoint == node => stage + 1,
+        _ => stage,
+    };
+    let mut labels = vec![Label {
+        node: source,
+        stage: advance(0, source),
+        hops: 0,
+        distance: 0.0,
+        parent: None,
+    }];
+    let step = max_hops.is_some() as usize;
+    // fewest hops any settled label of a (node, stage) took
+    let mut settled: HashMap<(u32, usize), usize> = HashMap::new();
+    // the last label queued for a (node, stage), labels it dominates are not queued
+    let mut queued: HashMap<(u32, usize), (f32, usize)> = HashMap::new();
+    let mut next_labels = BinaryHeap::from([Reverse((OrderedFloat(0.0), 0))]);
+
+    while let Some(Reverse((_, index))) = next_labels.pop() {
+        let label = &labels[index];
+        let (node, stage, hops, distance) = (label.node, label.stage, label.hops, label.distance);
+        match settled.get(&(node, stage)) {
+            Some(&fewest) if fewest <= hops => continue,
+            _ => settled.insert((node, stage), hops),
+        };
+        if node == target && stage == via.len() {
+            let mut path = vec![node];
+            let mut current = label.parent;
+            while let Some(parent) = current {
+                path.push(labels[parent].node);
+                current = labels[parent].parent;
+            }
+            path.reverse();
+            return Some((distance, path));
+        }
+        if max_hops.is_some_and(|max_hops| hops >= max_hops) {
+            continue;
+        }
+
+        for (link, weight) in graph.outlinks(node) {
+            let key = (link, advance(stage, link));
+            let total_distance = distance + weight + 1_f32;
+            if !allowed(link)
+                || settled
+                    .get(&key)
+                    .is_some_and(|&fewest| fewest <= hops + step)
+                || queued.get(&key).is_some_and(|&(known, fewest)| {
+                    known <= total_distance && fewest <= hops + step
+                })
+            {
+                continue;
+            }
+            queued.insert(key, (total_distance, hops + step));
+            next_labels.push(Reverse((OrderedFloat(total_distance), labels.len())));
+            labels.push(Label {
+                node: link,
+                stage: key.1,
+                hops: hops + step,
+                distance: total_distance,
+                parent: Some(index),
+            });
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Cheapest walk of at most `max_hops` links by dynamic programming over
+    /// (hops, waypoints reached, node)
+    fn brute_force(
+        graph: &CsrGraph,
+        source: u32,
+        target: u32,
+        via: &[u32],
+        max_hops: usize,
+        allowed: impl Fn(u32) -> bool,
+    ) -> f32 {
+        let stages = via.len() + 1;
+        let advance = |stage: usize, node: u32| match via.get(stage) {
+            Some(&waypoint) if waypoint == node => stage + 1,
+            _ => stage,
+        };
+        let mut current = vec![f32::INFINITY; graph.node_count() * stages];
+        current[source as usize * stages + advance(0, source)] = 0.0;
+        let mut best = current[target as usize * stages + via.len()];
+        for _ in 0..max_hops {
+            let mut next = vec![f32::INFINITY; current.len()];
+            for node in 0..graph.node_count() as u32 {
+                for stage in 0..stages {
+                    let distance = current[node as usize * stages + stage];
+                    if distance.is_infinite() {
+                        continue;
+                    }
+                    for (link, weight) in graph.outlinks(node).filter(|&(link, _)| allowed(link)) {
+                        let slot = &mut next[link as usize * stages + advance(stage, link)];
+                        *slot = slot.min(distance + weight + 1.0);
+                    }
+                }
+            }
+            current = next;
+            best = best.min(current[target as usize * stages + via.len()]);
+        }
+        best
+    }
+
+    fn walk_distance(graph: &CsrGraph, walk: &[u32]) -> f32 {
+        walk.windows(2)
+            .map(|hop| {
+                let (_, weight) = graph
+                    .outlinks(hop[0])
+                    .find(|&(link, _)| link == hop[1])
+                    .unwrap();
+                weight + 1.0
+            })
+            .sum()
+    }
+
+    #[test]
+    fn bidirectional_matches_distances_from() {
+        for seed in 0..50 {
+            let graph = CsrGraph::random(seed, 30, 3);
+            let reverse = graph.transpose();
+            let source = seed as u32 % 30;
+            let distances = distances_from(&graph, source);
+            for target in 0..30 {
+                match bidirectional_dijkstra(&graph, &reverse, source, target) {
+                    Some((distance, path)) => {
+                        assert!((distance - distances[target as usize]).abs() < 1e-4);
+                        assert!((walk_distance(&graph, &path) - distance).abs() < 1e-4);
+                        assert_eq!((path[0], path[path.len() - 1]), (source, target));
+                    }
+                    None => assert!(distances[target as usize].is_infinite(), "seed {seed}"),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn unconstrained_matches_dijkstra() {
+        for seed in 0..50 {
+            let graph = CsrGraph::random(seed, 20, 4);
+            let distances = distances_from(&graph, 0);
+            for target in 0..20 {
+                let found = constrained_dijkstra(&graph, 0, target, &[], None, |_| true);
+                match found {
+                    Some((distance, walk)) => {
+                        assert!((distance - distances[target as usize]).abs() < 1e-4);
+                        assert!((walk_distance(&graph, &walk) - distance).abs() < 1e-4);
+                    }
+                    None => assert!(distances[target as usize].is_infinite()),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn constrained_matches_brute_force() {
+        for seed in 0..100 {
+            let graph = CsrGraph::random(seed, 12, 4);
+            let n = graph.node_count() as u32;
+            let (source, target) = (seed as u32 % n, (seed as u32 * 7 + 3) % n);
+            let via: Vec<u32> = (0..seed as u32 % 3)
+                .map(|i| (seed as u32 + 5 * i) % n)
+                .collect();
+            let avoided = (seed as u32 * 3 + 1) % n;
+            let allowed = |node: u32| node != avoided;
+            let max_hops = (seed % 2 == 0).then_some(seed as usize % 7);
+
+            let limit = max_hops.unwrap_or(n as usize * (via.len() + 1));
+            let expected = brute_force(&graph, source, target, &via, limit, allowed);
+            match constrained_dijkstra(&graph, source, target, &via, max_hops, allowed) {
+                None => assert!(expected.is_infinite(), "seed {seed} missed a walk"),
+                Some((distance, walk)) => {
+                    assert!((distance - expected).abs() < 1e-4, "seed {seed}");
+                    assert!((walk_distance(&graph, &walk) - distance).abs() < 1e-4);
+                    assert_eq!((walk[0], walk[walk.len() - 1]), (source, target));
+                    assert!(walk[1..].iter().all(|&node| allowed(node)));
+                    assert!(max_hops.is_none_or(|max_hops| walk.len() - 1 <= max_hops));
+                    let reached = walk.iter().fold(0, |stage, &node| match via.get(stage) {
+                        Some(&waypoint) if waypoint == node => stage + 1,
+                        _ => stage,
+                    });
+                    assert_eq!(reached, via.len());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn waypoints_can_make_a_walk() {
+        // 2 is a dead end that only links back to 1
+        let graph = CsrGraph::from_edges(4, &[(0, 1, 0.0), (1, 2, 0.0), (2, 1, 0.0), (1, 3, 0.0)]);
+        let (distance, walk) = constrained_dijkstra(&graph, 0, 3, &[2], None, |_| true).unwrap();
+        assert_eq!(walk, vec![0, 1, 2, 1, 3]);
+        assert_eq!(distance, 4.0);
+    }
+
+    #[test]
+    fn max_hops_and_avoided_nodes() {
+        // the cheap way round is 0 -> 1 -> 2 -> 3, the direct link is expensive
+        let graph = CsrGraph::from_edges(4, &[(0, 1, 0.0), (1, 2, 0.0), (2, 3, 0.0), (0, 3, 5.0)]);
+        let path = |max_hops, avoided: u32| {
+            constrained_dijkstra(&graph, 0, 3, &[], max_hops, |node| node != avoided)
+                .map(|(_, path)| path)
+        };
+        assert_eq!(path(None, 9), Some(vec![0, 1, 2, 3]));
+        assert_eq!(path(Some(2), 9), Some(vec![0, 3]));
+        assert_eq!(path(None, 2), Some(vec![0, 3]));
+        assert_eq!(path(None, 3), None);
+        assert_eq!(path(Some(0), 9), None);
+    }
+}
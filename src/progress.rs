@@ -0,0 +1,290 @@
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::error::WikiSearchError;
+use crate::format::GraphHeader;
+use crate::href::HrefStats;
+
+/// Why an entry that is not a redirect did not become a page
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseFailure {
+    /// The entry has no item behind it
+    NoItem,
+    /// The item's data could not be read from the archive
+    Unreadable,
+    /// The data is not UTF-8, so not an HTML article
+    NotUtf8,
+}
+
+impl ParseFailure {
+    /// The error for the entry at `path` failing this way
+    pub fn error(self, path: &str) -> WikiSearchError {
+        let path = path.to_string();
+        match self {
+            ParseFailure::NoItem => WikiSearchError::MissingEntry { path },
+            ParseFailure::Unreadable => {
+                WikiSearchError::Archive(format!("cannot read the content of {path}"))
+            }
+            ParseFailure::NotUtf8 => WikiSearchError::NonUtf8 { path },
+        }
+    }
+}
+
+/// Running totals of a build, kept in checkpoints so a resumed build carries on counting
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct BuildStats {
+    pub pages: AtomicUsize,
+    pub redirects: AtomicUsize,
+    /// Distinct links of the parsed pages, before redirects are collapsed
+    pub links: AtomicUsize,
+    pub no_item: AtomicUsize,
+    pub unreadable: AtomicUsize,
+    pub not_utf8: AtomicUsize,
+}
+
+impl BuildStats {
+    pub fn record_page(&self, links: usize) {
+        self.pages.fetch_add(1, Ordering::Relaxed);
+        self.links.fetch_add(links, Ordering::Relaxed);
+    }
+
+    pub fn record_redirect(&self) {
+        self.redirects.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_failure(&self, reason: ParseFailure) {
+        let counter = match reason {
+            ParseFailure::NoItem => &self.no_item,
+            ParseFailure::Unreadable => &self.unreadable,
+            ParseFailure::NotUtf8 => &self.not_utf8,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Parse failures by reason
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct ParseFailures {
+    pub no_item: usize,
+    pub unreadable: usize,
+    pub not_utf8: usize,
+}
+
+impl ParseFailures {
+    pub fn total(&self) -> usize {
+        self.no_item + self.unreadable + self.not_utf8
+    }
+}
+
+/// How far a build has got, counts include the runs it was resumed from
+#[derive(Debug, Clone, Serialize)]
+pub struct BuildProgress {
+    pub entries_read: usize,
+    pub total_entries: usize,
+    pub pages: usize,
+    pub redirects: usize,
+    pub parse_failures: ParseFailures,
+    pub links: usize,
+    /// Seconds since this run started
+    pub elapsed_secs: f64,
+    /// Entries read per second by this run
+    pub entries_per_sec: f64,
+    /// Seconds until every entry is read at the current rate, `None` before there is a rate
+    pub eta_secs: Option<f64>,
+}
+
+impl BuildProgress {
+    /// A snapshot of `stats`, `skipped` entries were read by earlier runs
+    pub fn of(
+        stats: &BuildStats,
+        entries_read: usize,
+        total_entries: usize,
+        skipped: usize,
+        started: Instant,
+    ) -> Self {
+        let get = |counter: &AtomicUsize| counter.load(Ordering::Relaxed);
+        let elapsed_secs = started.elapsed().as_secs_f64();
+        let entries_per_sec = match elapsed_secs > 0.0 {
+            true => entries_read.saturating_sub(skipped) as f64 / elapsed_secs,
+            false => 0.0,
+        };
+        BuildProgress {
+            entries_read,
+            total_entries,
+            pages: get(&stats.pages),
+            redirects: get(&stats.redirects),
+            parse_failures: ParseFailures {
+                no_item: get(&stats.no_item),
+                unreadable: get(&stats.unreadable),
+                not_utf8: get(&stats.not_utf8),
+            },
+            links: get(&stats.links),
+            elapsed_secs,
+            entries_per_sec,
+            eta_secs: (entries_per_sec > 0.0)
+                .then(|| total_entries.saturating_sub(entries_read) as f64 / entries_per_sec),
+        }
+    }
+}
+
+/// Everything known about a finished build, written next to the graph by `JsonSummary`
+#[derive(Debug, Clone, Serialize)]
+pub struct BuildSummary<'a> {
+    pub header: &'a GraphHeader,
+    pub progress: BuildProgress,
+    pub hrefs: &'a HrefStats,
+    pub nodes: usize,
+    pub edges: usize,
+    /// Redirects left in the graph once chains are resolved
+    pub aliases: usize,
+}
+
+/// What happens during `WikiGraph::get_all`, in order
+#[derive(Debug, Clone, Copy)]
+pub enum BuildEvent<'a> {
+    /// About to read the archive, `skipped` entries were read by the runs resumed from
+    Started {
+        total_entries: usize,
+        skipped: usize,
+    },
+    /// A batch of entries was parsed
+    Progress(&'a BuildProgress),
+    /// Everything parsed so far was saved to `path`
+    Checkpoint { path: &'a str },
+    /// Stopped by Ctrl-C before the end of the archive
+    Interrupted(&'a BuildProgress),
+    /// Every entry was read and the graph frozen
+    Finished(&'a BuildSummary<'a>),
+}
+
+/// Told about every step of a build, see `WikiGraph::observe_build`
+pub trait BuildObserver: Send + Sync {
+    fn on_event(&mut self, event: BuildEvent);
+}
+
+fn format_duration(secs: f64) -> String {
+    let secs = secs as u64;
+    match secs {
+        0..60 => format!("{secs}s"),
+        60..3600 => format!("{}m{:02}s", secs / 60, secs % 60),
+        _ => format!("{}h{:02}m", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+// characters of the bar between the brackets
+const BAR_WIDTH: usize = 30;
+// the bar is redrawn at most this often
+const REDRAW_EVERY: Duration = Duration::from_millis(200);
+
+/// A progress bar on stderr, redrawn in place
+#[derive(Debug, Default)]
+pub struct TerminalProgress {
+    last_drawn: Option<Instant>,
+}
+
+impl TerminalProgress {
+    fn draw(&mut self, progress: &BuildProgress) {
+        if self
+            .last_drawn
+            .is_some_and(|drawn| drawn.elapsed() < REDRAW_EVERY)
+        {
+            return;
+        }
+        self.last_drawn = Some(Instant::now());
+        let fraction = match progress.total_entries {
+            0 => 1.0,
+            total => (progress.entries_read as f64 / total as f64).min(1.0),
+        };
+        let filled = (fraction * BAR_WIDTH as f64) as usize;
+        let eta = progress
+            .eta_secs
+            .map_or_else(|| "?".to_string(), format_duration);
+        eprint!(
+            "\r[{}{}] {:5.1}% {}/{} entries, {} pages, {} links, {:.0} entries/s, ETA {}\x1b[K",
+            "#".repeat(filled),
+            " ".repeat(BAR_WIDTH - filled),
+            fraction * 100.0,
+            progress.entries_read,
+            progress.total_entries,
+            progress.pages,
+            progress.links,
+            progress.entries_per_sec,
+            eta,
+        );
+        let _ = std::io::stderr().flush();
+    }
+}
+
+impl BuildObserver for TerminalProgress {
+    fn on_event(&mut self, event: BuildEvent) {
+        match event {
+            BuildEvent::Started {
+                total_entries,
+                skipped: 0,
+            } => eprintln!("Reading {total_entries} entries"),
+            BuildEvent::Started {
+                total_entries,
+                skipped,
+            } => eprintln!("Resuming after {skipped} of {total_entries} entries"),
+            BuildEvent::Progress(progress) => self.draw(progress),
+            BuildEvent::Checkpoint { path } => {
+                eprint!("\r\x1b[KCheckpoint saved to {path}\n");
+                self.last_drawn = None;
+            }
+            BuildEvent::Interrupted(progress) => {
+                self.last_drawn = None;
+                self.draw(progress);
+                eprintln!("\nInterrupted");
+            }
+            BuildEvent::Finished(summary) => {
+                self.last_drawn = None;
+                self.draw(&summary.progress);
+                let failures = summary.progress.parse_failures;
+                eprintln!(
+                    "\nBuilt {} nodes and {} edges in {}, {} entries could not be parsed \
+                     ({} without an item, {} unreadable, {} not UTF-8)",
+                    summary.nodes,
+                    summary.edges,
+                    format_duration(summary.progress.elapsed_secs),
+                    failures.total(),
+                    failures.no_item,
+                    failures.unreadable,
+                    failures.not_utf8,
+                );
+                eprintln!("{}", summary.hrefs);
+            }
+        }
+    }
+}
+
+/// Writes the `BuildSummary` of a finished build as JSON
+#[derive(Debug)]
+pub struct JsonSummary {
+    path: String,
+}
+
+impl JsonSummary {
+    /// Summarises the build of the graph saved at `graph_path` in `<graph_path>.summary.json`
+    pub fn next_to(graph_path: &str) -> Self {
+        JsonSummary {
+            path: format!("{graph_path}.summary.json"),
+        }
+    }
+
+    fn write(&self, summary: &BuildSummary) -> std::io::Result<()> {
+        let file = std::io::BufWriter::new(std::fs::File::create(&self.path)?);
+        serde_json::to_writer_pretty(file, summary).map_err(std::io::Error::other)
+    }
+}
+
+impl BuildObserver for JsonSummary {
+    fn on_event(&mut self, event: BuildEvent) {
+        if let BuildEvent::Finished(summary) = event
+            && let Err(e) = self.write(summary)
+        {
+            eprintln!("Could not write the build summary to {}: {e}", self.path);
+        }
+    }
+}
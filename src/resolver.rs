@@ -0,0 +1,125 @@
+use lasso::Spur;
+use serde::Serialize;
+
+use crate::error::{Result, WikiSearchError};
+use crate::{WikiGraph, resolve_redirect};
+
+/// How many fuzzy matches to look at, and offer when none of them is exact
+const SUGGESTION_COUNT: usize = 5;
+
+/// An article offered when a name could not be resolved
+#[derive(Debug, Clone, Serialize)]
+pub struct Candidate {
+    pub path: String,
+    pub title: String,
+}
+
+/// A name that matched no page of the graph
+#[derive(Debug, Clone)]
+pub struct Unresolved {
+    pub query: String,
+    pub did_you_mean: Vec<Candidate>,
+}
+
+impl std::fmt::Display for Unresolved {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} is not in the graph", self.query)?;
+        if !self.did_you_mean.is_empty() {
+            let titles: Vec<&str> = self.did_you_mean.iter().map(|c| c.title.as_str()).collect();
+            write!(f, ", did you mean: {}?", titles.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Unresolved {}
+
+/// Wikipedia paths use underscores and always start with a capital letter
+fn normalize(name: &str) -> String {
+    let name = name.split_whitespace().collect::<Vec<_>>().join("_");
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => name,
+    }
+}
+
+fn as_title(path: &str) -> String {
+    path.replace('_', " ")
+}
+
+/// Case and spacing insensitive key for comparing names
+fn fold(name: &str) -> String {
+    as_title(name)
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+impl WikiGraph {
+    /// Finds the page meant by a path, a title or a close enough name.
+    ///
+    /// Exact paths win, then the normalized path and title as stored in the archive
+    /// (following redirects), then the archive's suggestion index. When nothing
+    /// matches exactly the closest suggestions come back as candidates in
+    /// `WikiSearchError::UnknownNode`.
+    pub fn resolve_name(&self, name: &str) -> Result<Spur> {
+        if let Some(page) = self.lookup(name) {
+            return Ok(page);
+        }
+        let path = normalize(name);
+        if let Some(page) = self.lookup(&path) {
+            return Ok(page);
+        }
+        if let Some(page) = self.lookup_entry(&path) {
+            return Ok(page);
+        }
+
+        let candidates = self.suggest_pages(name, SUGGESTION_COUNT);
+        match candidates
+            .iter()
+            .find(|(_, c)| fold(&c.title) == fold(name))
+        {
+            Some(&(page, _)) => Ok(page),
+            None => Err(WikiSearchError::UnknownNode(Unresolved {
+                query: name.to_string(),
+                did_you_mean: candidates.into_iter().map(|(_, c)| c).collect(),
+            })),
+        }
+    }
+
+    /// Looks `path` up in the archive, both as a path and as a title
+    fn lookup_entry(&self, path: &str) -> Option<Spur> {
+        let mut entries = Vec::new();
+        if self.a.has_entry_bypath(path) {
+            entries.extend(self.a.get_entry_bypath_str(path).ok());
+        }
+        // archives from before the namespace scheme keep articles under A/
+        if !self.a.has_new_namespace_scheme() {
+            entries.extend(self.a.get_entry_bypath_str(&format!("A/{path}")).ok());
+        }
+        entries.extend(self.a.get_entry_bytitle_str(&as_title(path)).ok());
+
+        entries
+            .into_iter()
+            .filter_map(resolve_redirect)
+            .find_map(|e| self.lookup(&e.get_path()))
+    }
+
+    /// Suggestions from the archive's title index that are part of the graph
+    fn suggest_pages(&self, query: &str, count: usize) -> Vec<(Spur, Candidate)> {
+        self.suggest(query, count)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|suggestion| {
+                let page = suggestion.page?;
+                let candidate = Candidate {
+                    path: self.resolve(page).to_string(),
+                    title: suggestion.title,
+                };
+                Some((page, candidate))
+            })
+            .collect()
+    }
+}
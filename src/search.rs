@@ -0,0 +1,205 @@
+use lasso::Spur;
+use serde::Serialize;
+use std::collections::VecDeque;
+use zim_rs::search::{Query, Search, Searcher};
+use zim_rs::suggestion::SuggestionSearcher;
+
+use crate::error::{self, WikiSearchError};
+use crate::{WikiGraph, resolve_redirect};
+
+/// How many results a `SearchResults` iterator fetches from the index at once
+const SEARCH_BATCH: usize = 50;
+
+/// An article matching a full-text query
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchHit {
+    pub path: String,
+    pub title: String,
+    /// Path of the graph node the article ends up at after redirects
+    pub node: Option<String>,
+    #[serde(skip)]
+    pub page: Option<Spur>,
+}
+
+/// One page of results, `offset` is the rank of the first hit
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchPage {
+    pub query: String,
+    pub offset: usize,
+    /// The index's estimate of the total number of matches
+    pub estimated_matches: usize,
+    pub hits: Vec<SearchHit>,
+}
+
+/// A title from the suggestion index completing what was typed so far
+#[derive(Debug, Clone, Serialize)]
+pub struct Suggestion {
+    pub title: String,
+    pub path: String,
+    pub snippet: Option<String>,
+    /// Whether the article is a page of the graph with links of its own
+    pub in_graph: bool,
+    #[serde(skip)]
+    pub page: Option<Spur>,
+}
+
+fn search_error(query: &str) -> WikiSearchError {
+    WikiSearchError::Archive(format!("full-text search for {query:?} failed"))
+}
+
+/// libzim takes ranks and counts as `i32`
+fn to_i32(value: usize, name: &str) -> error::Result<i32> {
+    i32::try_from(value)
+        .map_err(|_| WikiSearchError::InvalidInput(format!("{name} {value} is too large")))
+}
+
+/// Every hit of a full-text query in rank order, fetched from the index in batches.
+///
+/// A batch the index fails to return ends the results with that error, a hit that can't be
+/// read is returned as an error in its place.
+pub struct SearchResults<'a> {
+    wiki_graph: &'a WikiGraph,
+    search: Search,
+    query: String,
+    offset: usize,
+    buffered: VecDeque<error::Result<SearchHit>>,
+    exhausted: bool,
+}
+
+impl SearchResults<'_> {
+    pub fn estimated_matches(&self) -> usize {
+        self.search.get_estimated_matches().max(0) as usize
+    }
+
+    /// Starts the results at rank `offset`, the hits before it are never fetched
+    pub fn starting_at(mut self, offset: usize) -> Self {
+        self.offset = offset;
+        self.buffered.clear();
+        self.exhausted = false;
+        self
+    }
+
+    /// Fetches the `count` hits starting at rank `offset`
+    fn fetch(&self, offset: usize, count: usize) -> error::Result<Vec<error::Result<SearchHit>>> {
+        let results = self
+            .search
+            .get_results(to_i32(offset, "offset")?, to_i32(count, "count")?)
+            .map_err(|()| search_error(&self.query))?;
+        Ok(results
+            .into_iter()
+            .enumerate()
+            .map(|(i, entry)| {
+                entry.map(|e| self.wiki_graph.search_hit(e)).map_err(|()| {
+                    WikiSearchError::Archive(format!(
+                        "hit {} of full-text search for {:?} could not be read",
+                        offset + i,
+                        self.query
+                    ))
+                })
+            })
+            .collect())
+    }
+}
+
+impl Iterator for SearchResults<'_> {
+    type Item = error::Result<SearchHit>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.buffered.is_empty() && !self.exhausted {
+            let batch = match self.fetch(self.offset, SEARCH_BATCH) {
+                Ok(batch) => batch,
+                Err(e) => {
+                    self.exhausted = true;
+                    return Some(Err(e));
+                }
+            };
+            self.exhausted = batch.len() < SEARCH_BATCH;
+            self.offset += batch.len();
+            self.buffered.extend(batch);
+        }
+        self.buffered.pop_front()
+    }
+}
+
+impl WikiGraph {
+    fn search_hit(&self, entry: zim_rs::entry::Entry) -> SearchHit {
+        let title = entry.get_title();
+        let path = entry.get_path();
+        let page = resolve_redirect(entry).and_then(|e| self.lookup(&e.get_path()));
+        SearchHit {
+            path,
+            title,
+            node: page.map(|page| self.resolve(page).to_string()),
+            page,
+        }
+    }
+
+    /// Runs `query` against the archive's full-text index
+    pub fn search(&self, query: &str) -> error::Result<SearchResults<'_>> {
+        if !self.a.has_fulltext_index() {
+            return Err(WikiSearchError::Unsupported(format!(
+                "{} has no full-text index",
+                self.a.get_filename()
+            )));
+        }
+        let mut searcher = Searcher::new(&self.a).map_err(|()| search_error(query))?;
+        let zim_query = Query::new(query).map_err(|()| search_error(query))?;
+        let search = searcher
+            .search(&zim_query)
+            .map_err(|()| search_error(query))?;
+        Ok(SearchResults {
+            wiki_graph: self,
+            search,
+            query: query.to_string(),
+            offset: 0,
+            buffered: VecDeque::new(),
+            exhausted: false,
+        })
+    }
+
+    /// Titles starting with or close to `prefix`, best first
+    pub fn suggest(&self, prefix: &str, limit: usize) -> error::Result<Vec<Suggestion>> {
+        let suggest_error =
+            || WikiSearchError::Archive(format!("suggestions for {prefix:?} failed"));
+        let limit = to_i32(limit, "limit")?;
+        let mut searcher = SuggestionSearcher::new(&self.a).map_err(|()| suggest_error())?;
+        let results = searcher
+            .suggest(prefix)
+            .and_then(|search| search.get_results(0, limit))
+            .map_err(|()| suggest_error())?;
+        Ok(results
+            .into_iter()
+            .filter_map(Result::ok)
+            .map(|item| {
+                let path = item.get_path();
+                let page = self.lookup(&path);
+                Suggestion {
+                    title: item.get_title(),
+                    snippet: item.has_snippet().then(|| item.get_snippet()),
+                    in_graph: page.is_some_and(|page| self.out_degree(page) > 0),
+                    path,
+                    page,
+                }
+            })
+            .collect())
+    }
+
+    /// The `limit` hits of `query` starting at rank `offset`
+    pub fn search_page(
+        &self,
+        query: &str,
+        offset: usize,
+        limit: usize,
+    ) -> error::Result<SearchPage> {
+        let results = self.search(query)?;
+        Ok(SearchPage {
+            query: query.to_string(),
+            offset,
+            estimated_matches: results.estimated_matches(),
+            hits: results
+                .fetch(offset, limit)?
+                .into_iter()
+                .collect::<error::Result<_>>()?,
+        })
+    }
+}
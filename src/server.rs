@@ -0,0 +1,288 @@
+use lasso::Spur;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::WikiGraph;
+use crate::error::WikiSearchError;
+use crate::hubs::{DEFAULT_HUB_STRENGTH, HubMeasure, HubPenalty};
+use crate::resolver::Candidate;
+
+const DEFAULT_NEIGHBOR_LIMIT: usize = 50;
+/// Caps on `/neighbors`, a search out to a large distance visits most of the graph
+const MAX_NEIGHBOR_LIMIT: usize = 1000;
+const MAX_NEIGHBOR_DISTANCE: f32 = 10.0;
+const DEFAULT_SEARCH_LIMIT: usize = 20;
+const DEFAULT_SUGGEST_LIMIT: usize = 10;
+
+/// One page along a path, `distance` is measured from the start of the path
+#[derive(Debug, Serialize)]
+struct Hop {
+    path: String,
+    title: String,
+    distance: f32,
+}
+
+#[derive(Debug, Serialize)]
+struct PathResponse {
+    from: String,
+    to: String,
+    distance: Option<f32>,
+    hops: Vec<Hop>,
+}
+
+#[derive(Debug, Serialize)]
+struct Neighbor {
+    path: String,
+    title: String,
+    distance: f32,
+    hops: Vec<Hop>,
+}
+
+#[derive(Debug, Serialize)]
+struct NeighborsResponse {
+    page: String,
+    min: f32,
+    max: f32,
+    neighbors: Vec<Neighbor>,
+}
+
+#[derive(Debug, Serialize)]
+struct PageResponse {
+    path: String,
+    title: String,
+    out_degree: usize,
+    in_degree: usize,
+    pagerank: Option<f32>,
+}
+
+#[derive(Debug, Serialize)]
+struct ErrorResponse {
+    error: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    did_you_mean: Vec<Candidate>,
+}
+
+/// A failed request, turned into a JSON error body with this status
+struct ApiError(u16, String, Vec<Candidate>);
+
+impl ApiError {
+    fn new(status: u16, msg: impl Into<String>) -> Self {
+        ApiError(status, msg.into(), Vec::new())
+    }
+
+    fn bad_request(msg: impl Into<String>) -> Self {
+        ApiError::new(400, msg)
+    }
+}
+
+impl From<WikiSearchError> for ApiError {
+    fn from(e: WikiSearchError) -> Self {
+        let status = match &e {
+            WikiSearchError::UnknownNode(_) | WikiSearchError::MissingEntry { .. } => 404,
+            WikiSearchError::InvalidInput(_) => 400,
+            WikiSearchError::Unsupported(_) => 501,
+            WikiSearchError::EmptyGraph => 503,
+            _ => 500,
+        };
+        match e {
+            WikiSearchError::UnknownNode(unresolved) => {
+                ApiError(status, unresolved.to_string(), unresolved.did_you_mean)
+            }
+            e => ApiError::new(status, e.to_string()),
+        }
+    }
+}
+
+type ApiResult = Result<String, ApiError>;
+
+fn to_json<T: Serialize>(value: &T) -> ApiResult {
+    serde_json::to_string(value).map_err(|e| ApiError::new(500, e.to_string()))
+}
+
+/// Query string of a request, percent-decoded
+struct Query(HashMap<String, String>);
+
+impl Query {
+    fn parse(url: &str) -> (&str, Self) {
+        let (route, query) = url.split_once('?').unwrap_or((url, ""));
+        let params = url::form_urlencoded::parse(query.as_bytes())
+            .into_owned()
+            .collect();
+        (route, Query(params))
+    }
+
+    fn required(&self, name: &str) -> Result<&str, ApiError> {
+        self.0
+            .get(name)
+            .map(String::as_str)
+            .ok_or_else(|| ApiError::bad_request(format!("missing parameter `{name}`")))
+    }
+
+    fn parsed<T: std::str::FromStr>(&self, name: &str, default: T) -> Result<T, ApiError> {
+        match self.0.get(name) {
+            Some(value) => value
+                .parse()
+                .map_err(|_| ApiError::bad_request(format!("invalid value for `{name}`"))),
+            None => Ok(default),
+        }
+    }
+}
+
+fn lookup(wiki_graph: &WikiGraph, name: &str) -> Result<Spur, ApiError> {
+    Ok(wiki_graph.resolve_name(name)?)
+}
+
+fn hops(wiki_graph: &WikiGraph, path: &[Spur]) -> Vec<Hop> {
+    path.iter()
+        .zip(wiki_graph.hop_distances(path))
+        .map(|(&page, distance)| Hop {
+            path: wiki_graph.resolve(page).to_string(),
+            title: wiki_graph.title(page),
+            distance,
+        })
+        .collect()
+}
+
+fn shortest_path(wiki_graph: &WikiGraph, query: &Query) -> ApiResult {
+    let (from, to) = (query.required("from")?, query.required("to")?);
+    let (first_link, target_link) = (lookup(wiki_graph, from)?, lookup(wiki_graph, to)?);
+    let path = match query.0.get("hub") {
+        Some(measure) => {
+            let measure: HubMeasure = measure.parse()?;
+            let penalty = HubPenalty {
+                measure,
+                strength: query.parsed("hub_strength", DEFAULT_HUB_STRENGTH)?,
+            };
+            wiki_graph.find_shortest_path_penalized(first_link, target_link, penalty)?
+        }
+        None => wiki_graph.find_shortest_path(first_link, target_link),
+    };
+    let hops = path.map(|path| hops(wiki_graph, &path)).unwrap_or_default();
+    to_json(&PathResponse {
+        from: wiki_graph.resolve(first_link).to_string(),
+        to: wiki_graph.resolve(target_link).to_string(),
+        distance: hops.last().map(|hop| hop.distance),
+        hops,
+    })
+}
+
+fn neighbors(wiki_graph: &WikiGraph, query: &Query) -> ApiResult {
+    let page = lookup(wiki_graph, query.required("page")?)?;
+    let min = query.parsed("min", 0.0)?;
+    let max = query.parsed("max", 3.0_f32)?.min(MAX_NEIGHBOR_DISTANCE);
+    let limit = query
+        .parsed("limit", DEFAULT_NEIGHBOR_LIMIT)?
+        .min(MAX_NEIGHBOR_LIMIT);
+    let neighbors = wiki_graph
+        .iter_close_titles(page, min, Some(max))
+        .take(limit)
+        .map(|info| {
+            let last = info.path.last().copied().unwrap_or(page);
+            Neighbor {
+                path: wiki_graph.resolve(last).to_string(),
+                title: wiki_graph.title(last),
+                distance: info.distance,
+                hops: hops(wiki_graph, &info.path),
+            }
+        })
+        .collect();
+    to_json(&NeighborsResponse {
+        page: wiki_graph.resolve(page).to_string(),
+        min,
+        max,
+        neighbors,
+    })
+}
+
+fn search(wiki_graph: &WikiGraph, query: &Query) -> ApiResult {
+    let text = query.required("q")?;
+    let offset = query.parsed("offset", 0)?;
+    let limit = query.parsed("limit", DEFAULT_SEARCH_LIMIT)?;
+    to_json(&wiki_graph.search_page(text, offset, limit)?)
+}
+
+fn suggest(wiki_graph: &WikiGraph, query: &Query) -> ApiResult {
+    let prefix = query.required("q")?;
+    let limit = query.parsed("limit", DEFAULT_SUGGEST_LIMIT)?;
+    to_json(&wiki_graph.suggest(prefix, limit)?)
+}
+
+fn page_info(wiki_graph: &WikiGraph, page: Spur) -> ApiResult {
+    to_json(&PageResponse {
+        path: wiki_graph.resolve(page).to_string(),
+        title: wiki_graph.title(page),
+        out_degree: wiki_graph.out_degree(page),
+        in_degree: wiki_graph.in_degree(page),
+        pagerank: wiki_graph.pagerank(page),
+    })
+}
+
+fn random(wiki_graph: &WikiGraph) -> ApiResult {
+    let page = wiki_graph
+        .random_page(1000)
+        .ok_or(WikiSearchError::EmptyGraph)?;
+    page_info(wiki_graph, page)
+}
+
+fn route(wiki_graph: &WikiGraph, request: &Request) -> ApiResult {
+    if *request.method() != Method::Get {
+        return Err(ApiError::new(405, "only GET is supported"));
+    }
+    let (route, query) = Query::parse(request.url());
+    match route {
+        "/path" => shortest_path(wiki_graph, &query),
+        "/neighbors" => neighbors(wiki_graph, &query),
+        "/search" => search(wiki_graph, &query),
+        "/suggest" => suggest(wiki_graph, &query),
+        "/random" => random(wiki_graph),
+        "/page" => page_info(wiki_graph, lookup(wiki_graph, query.required("page")?)?),
+        _ => Err(ApiError::new(404, format!("no endpoint {route}"))),
+    }
+}
+
+fn respond(wiki_graph: &WikiGraph, request: Request) {
+    let (status, body) = match route(wiki_graph, &request) {
+        Ok(body) => (200, body),
+        Err(ApiError(status, error, did_you_mean)) => (
+            status,
+            serde_json::to_string(&ErrorResponse {
+                error,
+                did_you_mean,
+            })
+            .unwrap_or_default(),
+        ),
+    };
+    let content_type = Header::from_bytes("Content-Type", "application/json").unwrap();
+    let response = Response::from_string(body)
+        .with_status_code(status)
+        .with_header(content_type);
+    if let Err(e) = request.respond(response) {
+        eprintln!("Failed to send response: {e}");
+    }
+}
+
+/// Serves the JSON API on `addr` until the process exits, answering requests on
+/// `threads` workers that share one loaded graph.
+pub fn serve(wiki_graph: WikiGraph, addr: &str, threads: usize) -> crate::error::Result<()> {
+    let server = Arc::new(Server::http(addr).map_err(std::io::Error::other)?);
+    let wiki_graph = Arc::new(wiki_graph);
+    println!("Listening on http://{}", server.server_addr());
+
+    let workers: Vec<_> = (0..threads.max(1))
+        .map(|_| {
+            let server = Arc::clone(&server);
+            let wiki_graph = Arc::clone(&wiki_graph);
+            std::thread::spawn(move || {
+                for request in server.incoming_requests() {
+                    respond(&wiki_graph, request);
+                }
+            })
+        })
+        .collect();
+    for worker in workers {
+        let _ = worker.join();
+    }
+    Ok(())
+}
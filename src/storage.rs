@@ -0,0 +1,436 @@
+use lasso::{Key, Spur, ThreadedRodeo};
+use memmap2::Mmap;
+use rayon::prelude::*;
+use std::marker::PhantomData;
+use std::ops::{Deref, Range};
+use std::sync::Arc;
+
+use crate::error::{Result, WikiSearchError};
+use crate::extract::LinkText;
+
+/// Types that can be viewed straight from file bytes: no padding, every bit pattern valid.
+///
+/// # Safety
+/// Only implement for primitive numeric types.
+pub unsafe trait Plain: Copy + Send + Sync + 'static {}
+
+unsafe impl Plain for u8 {}
+unsafe impl Plain for u32 {}
+unsafe impl Plain for u64 {}
+unsafe impl Plain for f32 {}
+
+/// A slice that is either owned or borrowed from a memory-mapped graph file.
+pub enum Section<T: Plain> {
+    Owned(Vec<T>),
+    Mapped {
+        map: Arc<Mmap>,
+        range: Range<usize>,
+        _type: PhantomData<T>,
+    },
+}
+
+impl<T: Plain> Section<T> {
+    /// Views `range` of `map` as a `[T]`, checking it is in bounds and aligned.
+    pub fn mapped(map: &Arc<Mmap>, range: Range<usize>) -> Result<Self> {
+        let size = std::mem::size_of::<T>();
+        let in_bounds = range.start <= range.end && range.end <= map.len();
+        let aligned =
+            (map.as_ptr() as usize + range.start).is_multiple_of(std::mem::align_of::<T>());
+        if !in_bounds || !aligned || !range.len().is_multiple_of(size) {
+            return Err(WikiSearchError::CorruptGraph(format!(
+                "section {range:?} is out of bounds or misaligned"
+            )));
+        }
+        Ok(Section::Mapped {
+            map: Arc::clone(map),
+            range,
+            _type: PhantomData,
+        })
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        let items: &[T] = self;
+        // SAFETY: `T: Plain` has no padding, so every byte of the slice is initialised
+        unsafe { std::slice::from_raw_parts(items.as_ptr().cast(), std::mem::size_of_val(items)) }
+    }
+}
+
+impl<T: Plain> Default for Section<T> {
+    fn default() -> Self {
+        Section::Owned(Vec::new())
+    }
+}
+
+impl<T: Plain> From<Vec<T>> for Section<T> {
+    fn from(items: Vec<T>) -> Self {
+        Section::Owned(items)
+    }
+}
+
+impl<T: Plain> Deref for Section<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        match self {
+            Section::Owned(items) => items,
+            Section::Mapped { map, range, .. } => {
+                let bytes = &map[range.clone()];
+                // SAFETY: `mapped` checked bounds, alignment and size, `T: Plain` accepts any
+                // bytes, and the Arc keeps the mapping alive for as long as the slice
+                unsafe {
+                    std::slice::from_raw_parts(
+                        bytes.as_ptr().cast(),
+                        bytes.len() / std::mem::size_of::<T>(),
+                    )
+                }
+            }
+        }
+    }
+}
+
+impl<T: Plain> std::fmt::Debug for Section<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Section").field("len", &self.len()).finish()
+    }
+}
+
+/// Whether `offsets` can delimit rows of a section of `len` items: starting at 0 and ending
+/// at `len`.
+///
+/// Only the ends are checked so loading a file does not read every offset, `row` keeps the
+/// rows in between inside the section.
+pub fn valid_offsets(offsets: &[u64], len: usize) -> bool {
+    offsets.first() == Some(&0) && offsets.last() == Some(&(len as u64))
+}
+
+/// Row `i` of a section delimited by `offsets`, empty when `i` is not a row and cut back
+/// to the section when a corrupt file holds offsets out of order
+pub fn row(offsets: &[u64], i: usize) -> Range<usize> {
+    let (Some(&start), Some(&end), Some(&len)) =
+        (offsets.get(i), offsets.get(i + 1), offsets.last())
+    else {
+        return 0..0;
+    };
+    let end = end.min(len) as usize;
+    start.min(end as u64) as usize..end
+}
+
+/// Whether every id in `ids` is a node of a graph with `node_count` nodes
+pub fn valid_ids(ids: &[u32], node_count: usize) -> bool {
+    ids.par_iter().all(|&id| (id as usize) < node_count)
+}
+
+/// Concatenates `strings` into the offsets and bytes of a table of strings
+fn pack<'a>(strings: impl Iterator<Item = &'a str>) -> (Vec<u64>, Vec<u8>) {
+    let mut offsets = vec![0];
+    let mut bytes = Vec::new();
+    for s in strings {
+        bytes.extend_from_slice(s.as_bytes());
+        offsets.push(bytes.len() as u64);
+    }
+    (offsets, bytes)
+}
+
+/// String `i` of a table packed by `pack`, empty if there is no such string
+fn string_at<'a>(offsets: &[u64], bytes: &'a [u8], i: usize) -> &'a str {
+    // written from `&str`s, only a corrupt file holds anything else
+    std::str::from_utf8(&bytes[row(offsets, i)]).unwrap_or_default()
+}
+
+/// Node names, string `i` is the path of node `i`.
+#[derive(Debug, Default)]
+pub struct StringTable {
+    // string i is bytes[offsets[i]..offsets[i + 1]]
+    offsets: Section<u64>,
+    bytes: Section<u8>,
+    // node ids ordered by their string, for lookups by path
+    sorted: Section<u32>,
+}
+
+impl StringTable {
+    pub fn from_interner(interner: &ThreadedRodeo) -> Self {
+        let (offsets, bytes) =
+            pack((0..interner.len()).map(|i| interner.resolve(&Spur::try_from_usize(i).unwrap())));
+        let mut table = StringTable {
+            offsets: offsets.into(),
+            bytes: bytes.into(),
+            sorted: Section::default(),
+        };
+        let mut sorted: Vec<u32> = (0..interner.len() as u32).collect();
+        sorted.sort_unstable_by(|&a, &b| table.resolve(a).cmp(table.resolve(b)));
+        table.sorted = sorted.into();
+        table
+    }
+
+    pub fn from_sections(
+        offsets: Section<u64>,
+        bytes: Section<u8>,
+        sorted: Section<u32>,
+    ) -> Result<Self> {
+        if !valid_offsets(&offsets, bytes.len()) {
+            return Err(WikiSearchError::CorruptGraph(
+                "string table offsets are out of order".to_string(),
+            ));
+        }
+        // ids in `sorted` that are not strings resolve to ""
+        if sorted.len() != offsets.len() - 1 {
+            return Err(WikiSearchError::CorruptGraph(
+                "string table index is inconsistent".to_string(),
+            ));
+        }
+        Ok(StringTable {
+            offsets,
+            bytes,
+            sorted,
+        })
+    }
+
+    pub fn sections(&self) -> [&[u8]; 3] {
+        [
+            self.offsets.as_bytes(),
+            self.bytes.as_bytes(),
+            self.sorted.as_bytes(),
+        ]
+    }
+
+    pub fn len(&self) -> usize {
+        self.offsets.len().saturating_sub(1)
+    }
+
+    pub fn resolve(&self, node: u32) -> &str {
+        string_at(&self.offsets, &self.bytes, node as usize)
+    }
+
+    pub fn try_resolve(&self, node: u32) -> Option<&str> {
+        ((node as usize) < self.len()).then(|| self.resolve(node))
+    }
+
+    pub fn get(&self, s: &str) -> Option<u32> {
+        let i = self
+            .sorted
+            .binary_search_by(|&node| self.resolve(node).cmp(s))
+            .ok()?;
+        Some(self.sorted[i])
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &str> + '_ {
+        (0..self.len() as u32).map(|node| self.resolve(node))
+    }
+}
+
+/// Redirect node -> canonical node, sorted by the redirect for binary search.
+#[derive(Debug, Default)]
+pub struct AliasTable {
+    sources: Section<u32>,
+    targets: Section<u32>,
+}
+
+impl AliasTable {
+    pub fn from_pairs(mut pairs: Vec<(u32, u32)>) -> Self {
+        pairs.sort_unstable();
+        let (sources, targets): (Vec<u32>, Vec<u32>) = pairs.into_iter().unzip();
+        AliasTable {
+            sources: sources.into(),
+            targets: targets.into(),
+        }
+    }
+
+    /// Checks the table read back pairs every redirect with a target. Ids are not checked
+    /// against the graph, a target that is not a node has no links or name.
+    pub fn from_sections(sources: Section<u32>, targets: Section<u32>) -> Result<Self> {
+        if sources.len() != targets.len() {
+            return Err(WikiSearchError::CorruptGraph(
+                "alias table is inconsistent".to_string(),
+            ));
+        }
+        Ok(AliasTable { sources, targets })
+    }
+
+    pub fn sections(&self) -> [&[u8]; 2] {
+        [self.sources.as_bytes(), self.targets.as_bytes()]
+    }
+
+    pub fn len(&self) -> usize {
+        self.sources.len()
+    }
+
+    pub fn get(&self, node: u32) -> Option<u32> {
+        let i = self.sources.binary_search(&node).ok()?;
+        Some(self.targets[i])
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        self.sources
+            .iter()
+            .copied()
+            .zip(self.targets.iter().copied())
+    }
+}
+
+/// Anchor text and sentence of every edge, in the order of the graph's edges.
+///
+/// Mapped like the names so loading a graph does not read them until a path is explained.
+#[derive(Debug, Default)]
+pub struct LinkTextTable {
+    anchor_offsets: Section<u64>,
+    anchors: Section<u8>,
+    context_offsets: Section<u64>,
+    contexts: Section<u8>,
+}
+
+impl LinkTextTable {
+    pub fn from_texts(texts: &[LinkText]) -> Self {
+        let (anchor_offsets, anchors) = pack(texts.iter().map(|text| text.anchor.as_str()));
+        let (context_offsets, contexts) = pack(texts.iter().map(|text| text.context.as_str()));
+        LinkTextTable {
+            anchor_offsets: anchor_offsets.into(),
+            anchors: anchors.into(),
+            context_offsets: context_offsets.into(),
+            contexts: contexts.into(),
+        }
+    }
+
+    /// Checks the table read back has one text for each of `edge_count` edges
+    pub fn from_sections(
+        anchor_offsets: Section<u64>,
+        anchors: Section<u8>,
+        context_offsets: Section<u64>,
+        contexts: Section<u8>,
+        edge_count: usize,
+    ) -> Result<Self> {
+        if !valid_offsets(&anchor_offsets, anchors.len())
+            || !valid_offsets(&context_offsets, contexts.len())
+            || anchor_offsets.len() != edge_count + 1
+            || context_offsets.len() != edge_count + 1
+        {
+            return Err(WikiSearchError::CorruptGraph(
+                "link text table is inconsistent".to_string(),
+            ));
+        }
+        Ok(LinkTextTable {
+            anchor_offsets,
+            anchors,
+            context_offsets,
+            contexts,
+        })
+    }
+
+    pub fn sections(&self) -> [&[u8]; 4] {
+        [
+            self.anchor_offsets.as_bytes(),
+            self.anchors.as_bytes(),
+            self.context_offsets.as_bytes(),
+            self.contexts.as_bytes(),
+        ]
+    }
+
+    pub fn len(&self) -> usize {
+        self.anchor_offsets.len().saturating_sub(1)
+    }
+
+    pub fn get(&self, edge: usize) -> Option<LinkText> {
+        (edge < self.len()).then(|| LinkText {
+            anchor: string_at(&self.anchor_offsets, &self.anchors, edge).to_string(),
+            context: string_at(&self.context_offsets, &self.contexts, edge).to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    /// Maps `bytes` from a file of their own
+    fn map_bytes(name: &str, bytes: &[u8]) -> Arc<Mmap> {
+        let path = std::env::temp_dir().join(format!("wiki-search-{}-{name}", std::process::id()));
+        std::fs::File::create(&path)
+            .unwrap()
+            .write_all(bytes)
+            .unwrap();
+        let file = std::fs::File::open(&path).unwrap();
+        // SAFETY: the file is only removed, never written, while mapped
+        let map = Arc::new(unsafe { Mmap::map(&file).unwrap() });
+        std::fs::remove_file(&path).unwrap();
+        map
+    }
+
+    #[test]
+    fn mapped_sections_read_the_file_in_place() {
+        let values: [u64; 3] = [1, u64::MAX, 42];
+        let mut bytes: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+        bytes.extend_from_slice(&7_u32.to_le_bytes());
+        let map = map_bytes("sections", &bytes);
+
+        let section: Section<u64> = Section::mapped(&map, 0..24).unwrap();
+        assert_eq!(&section[..], &values);
+        assert_eq!(section.as_bytes(), &bytes[..24]);
+        let section: Section<u32> = Section::mapped(&map, 24..28).unwrap();
+        assert_eq!(&section[..], &[7]);
+        let empty: Section<u32> = Section::mapped(&map, 28..28).unwrap();
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn mapped_sections_must_fit_the_file_and_their_type() {
+        let map = map_bytes("bad-sections", &[0; 32]);
+        // past the end
+        assert!(Section::<u8>::mapped(&map, 16..40).is_err());
+        // backwards
+        assert!(Section::<u8>::mapped(&map, Range { start: 8, end: 4 }).is_err());
+        // not a whole number of items
+        assert!(Section::<u64>::mapped(&map, 0..12).is_err());
+        // misaligned, the map itself starts on a page boundary
+        assert!(Section::<u64>::mapped(&map, 4..20).is_err());
+        assert!(Section::<u32>::mapped(&map, 4..20).is_ok());
+    }
+
+    #[test]
+    fn string_table_resolves_and_finds_names() {
+        let interner = ThreadedRodeo::new();
+        for name in ["Paris", "Lyon", "Café", ""] {
+            interner.get_or_intern(name);
+        }
+        let table = StringTable::from_interner(&interner);
+        assert_eq!(table.len(), 4);
+        assert_eq!(table.resolve(2), "Café");
+        assert_eq!(table.get("Lyon"), Some(1));
+        assert_eq!(table.get(""), Some(3));
+        assert_eq!(table.get("Nice"), None);
+        assert_eq!(table.try_resolve(4), None);
+        assert_eq!(
+            table.iter().collect::<Vec<_>>(),
+            vec!["Paris", "Lyon", "Café", ""]
+        );
+    }
+
+    #[test]
+    fn corrupt_tables_are_rejected_or_read_safely() {
+        let strings = |offsets: Vec<u64>, sorted: Vec<u32>| {
+            StringTable::from_sections(offsets.into(), b"ab".to_vec().into(), sorted.into())
+        };
+        assert!(strings(vec![0, 1, 2], vec![0, 1]).is_ok());
+        assert!(strings(vec![0, 1, 3], vec![0, 1]).is_err());
+        assert!(strings(vec![0, 1, 2], vec![0]).is_err());
+        // rows out of order and ids that are not strings are only found when read
+        let table = strings(vec![0, 2, 1, 2], vec![0, 7, 1]).unwrap();
+        assert_eq!(table.iter().collect::<Vec<_>>(), vec!["ab", "", "b"]);
+        assert_eq!(table.resolve(9), "");
+
+        let aliases = |sources: Vec<u32>, targets: Vec<u32>| {
+            AliasTable::from_sections(sources.into(), targets.into()).is_ok()
+        };
+        assert!(aliases(vec![0, 2], vec![1, 1]));
+        assert!(!aliases(vec![0], vec![1, 1]));
+    }
+
+    #[test]
+    fn rows_stay_inside_their_section() {
+        let offsets = [0, 4, 2, 9, 5];
+        assert_eq!(row(&offsets, 0), 0..4);
+        assert_eq!(row(&offsets, 1), 2..2);
+        assert_eq!(row(&offsets, 2), 2..5);
+        assert_eq!(row(&offsets, 3), 5..5);
+        assert_eq!(row(&offsets, 4), 0..0);
+    }
+}
@@ -0,0 +1,153 @@
+use std::sync::Arc;
+
+use crate::error::{Result, WikiSearchError};
+use crate::extract::LinkRegion;
+
+/// Where a link sits in the article it was found in
+#[derive(Debug, Clone, Copy)]
+pub struct LinkContext {
+    /// Position among the article's distinct links, from 0
+    pub index: usize,
+    pub total: usize,
+    /// Number of `h2` headings before the link, 0 for the lead
+    pub section: usize,
+    pub sections: usize,
+    pub region: LinkRegion,
+}
+
+/// Turns the position of a link into the cost of following it.
+///
+/// Weights must be finite and non-negative, every edge also costs 1 on top of its weight.
+pub trait LinkWeighter: Send + Sync {
+    /// Recorded in the graph header, `from_name` must give back an equivalent weighter
+    fn name(&self) -> String;
+
+    fn weight(&self, link: &LinkContext) -> f32;
+}
+
+/// Every link costs the same, distances count hops
+#[derive(Debug, Clone, Copy)]
+pub struct Uniform;
+
+impl LinkWeighter for Uniform {
+    fn name(&self) -> String {
+        "uniform".to_string()
+    }
+
+    fn weight(&self, _link: &LinkContext) -> f32 {
+        0.0
+    }
+}
+
+/// Position divided by the number of links, from just above 0 to 1
+#[derive(Debug, Clone, Copy)]
+pub struct LinearPosition;
+
+impl LinkWeighter for LinearPosition {
+    fn name(&self) -> String {
+        "linear-position".to_string()
+    }
+
+    fn weight(&self, link: &LinkContext) -> f32 {
+        (link.index + 1) as f32 / link.total as f32
+    }
+}
+
+/// Like `LinearPosition` but flattening out further down, so the first few links stand out
+#[derive(Debug, Clone, Copy)]
+pub struct LogPosition;
+
+impl LinkWeighter for LogPosition {
+    fn name(&self) -> String {
+        "log-position".to_string()
+    }
+
+    fn weight(&self, link: &LinkContext) -> f32 {
+        ((link.index + 1) as f32).ln_1p() / (link.total as f32).ln_1p()
+    }
+}
+
+/// Every link above this one costs `scroll_cost`, regardless of how long the article is
+#[derive(Debug, Clone, Copy)]
+pub struct AbsolutePosition {
+    pub scroll_cost: f32,
+}
+
+impl AbsolutePosition {
+    /// `None` unless `scroll_cost` is finite and non-negative, as weights must be
+    pub fn new(scroll_cost: f32) -> Option<Self> {
+        (scroll_cost.is_finite() && scroll_cost >= 0.0).then_some(AbsolutePosition { scroll_cost })
+    }
+}
+
+impl LinkWeighter for AbsolutePosition {
+    fn name(&self) -> String {
+        format!("absolute-position:{}", self.scroll_cost)
+    }
+
+    fn weight(&self, link: &LinkContext) -> f32 {
+        link.index as f32 * self.scroll_cost
+    }
+}
+
+/// Links in the lead are free, later sections cost more the further down they are
+#[derive(Debug, Clone, Copy)]
+pub struct SectionPosition;
+
+impl LinkWeighter for SectionPosition {
+    fn name(&self) -> String {
+        "section".to_string()
+    }
+
+    fn weight(&self, link: &LinkContext) -> f32 {
+        link.section as f32 / (link.sections + 1) as f32
+    }
+}
+
+pub const DEFAULT_SCROLL_COST: f32 = 0.01;
+
+/// The built-in strategies, for choosing one on the command line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Weighting {
+    Uniform,
+    Linear,
+    Log,
+    Absolute,
+    Section,
+}
+
+impl Weighting {
+    /// `scroll_cost` is only used by `Absolute` and must be finite and non-negative
+    pub fn weighter(self, scroll_cost: f32) -> Result<Arc<dyn LinkWeighter>> {
+        Ok(match self {
+            Weighting::Uniform => Arc::new(Uniform),
+            Weighting::Linear => Arc::new(LinearPosition),
+            Weighting::Log => Arc::new(LogPosition),
+            Weighting::Absolute => {
+                Arc::new(AbsolutePosition::new(scroll_cost).ok_or_else(|| {
+                    WikiSearchError::InvalidInput(format!(
+                        "scroll cost must be a non-negative number, not {scroll_cost}"
+                    ))
+                })?)
+            }
+            Weighting::Section => Arc::new(SectionPosition),
+        })
+    }
+}
+
+/// The weighter a graph header names, `None` for names this build does not know
+pub fn from_name(name: &str) -> Option<Arc<dyn LinkWeighter>> {
+    let weighter: Arc<dyn LinkWeighter> = match name.split_once(':') {
+        Some(("absolute-position", cost)) => Arc::new(AbsolutePosition::new(cost.parse().ok()?)?),
+        Some(_) => return None,
+        None => match name {
+            "uniform" => Arc::new(Uniform),
+            "linear-position" => Arc::new(LinearPosition),
+            "log-position" => Arc::new(LogPosition),
+            "section" => Arc::new(SectionPosition),
+            _ => return None,
+        },
+    };
+    Some(weighter)
+}